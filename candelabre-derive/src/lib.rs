@@ -0,0 +1,170 @@
+//! # Welcome!
+//!
+//! This crate holds the procedural macros of candelabre. Don't use it
+//! directly, the macros are exported by the crates using them.
+//!
+//! ## `#[derive(CandlVertex)]`
+//!
+//! Implement `CandlVertex` for a `#[repr(C)]` struct, with named fields, to
+//! get its vertex layout. Each field becomes an attribute, named after the
+//! field, with the format of its type (the type must implement
+//! `CandlAttribute`). The fields take some options:
+//!
+//! * `#[candl(name = "co")]` to use another name for the attribute
+//! * `#[candl(location = 0)]` to bind the attribute to a location
+//! * `#[candl(normalized)]` to normalize the integer components
+//! * `#[candl(skip)]` to ignore the field (padding, etc)
+//!
+//! And the struct can take `#[candl(divisor = 1)]`, to advance the attributes
+//! once per instance.
+//!
+//! The offsets of the fields come from `core::mem::offset_of!`, so the crates
+//! using the derive need Rust 1.77 or later.
+
+#![deny(missing_docs)]
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::quote;
+use syn::{
+    parse_macro_input, Attribute, Data, DeriveInput, Error, Fields, Lit, Meta, NestedMeta
+};
+
+/// derive the vertex layout of a `#[repr(C)]` struct
+#[proc_macro_derive(CandlVertex, attributes(candl))]
+pub fn derive_candl_vertex(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match vertex_impl(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into()
+    }
+}
+
+/// options of a field
+#[derive(Default)]
+struct FieldOptions {
+    name: Option<String>,
+    location: Option<u32>,
+    normalized: bool,
+    skip: bool
+}
+
+/// read the `#[candl(...)]` attributes, calling a closure for each option
+fn for_each_option<F>(attrs: &[Attribute], mut fun: F) -> Result<(), Error>
+where F: FnMut(&Meta) -> Result<(), Error> {
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident("candl")) {
+        match attr.parse_meta()? {
+            Meta::List(list) => for nested in &list.nested {
+                match nested {
+                    NestedMeta::Meta(meta) => fun(meta)?,
+                    NestedMeta::Lit(lit) => return Err(Error::new_spanned(lit, "unexpected literal"))
+                }
+            },
+            meta => return Err(Error::new_spanned(meta, "expected #[candl(...)]"))
+        }
+    }
+    Ok(())
+}
+
+/// read an integer option
+fn int_option(meta: &Meta, lit: &Lit) -> Result<u32, Error> {
+    match lit {
+        Lit::Int(int) => int.base10_parse(),
+        _ => Err(Error::new_spanned(meta, "expected an integer"))
+    }
+}
+
+fn field_options(attrs: &[Attribute]) -> Result<FieldOptions, Error> {
+    let mut options = FieldOptions::default();
+    for_each_option(attrs, |meta| {
+        match meta {
+            Meta::Path(path) if path.is_ident("normalized") => options.normalized = true,
+            Meta::Path(path) if path.is_ident("skip") => options.skip = true,
+            Meta::NameValue(nv) if nv.path.is_ident("name") => match &nv.lit {
+                Lit::Str(name) => options.name = Some(name.value()),
+                _ => return Err(Error::new_spanned(meta, "expected a string"))
+            },
+            Meta::NameValue(nv) if nv.path.is_ident("location") =>
+                options.location = Some(int_option(meta, &nv.lit)?),
+            _ => return Err(Error::new_spanned(meta, "unknown option for a field of a CandlVertex"))
+        }
+        Ok(())
+    })?;
+    Ok(options)
+}
+
+fn struct_divisor(attrs: &[Attribute]) -> Result<u32, Error> {
+    let mut divisor = 0;
+    for_each_option(attrs, |meta| {
+        match meta {
+            Meta::NameValue(nv) if nv.path.is_ident("divisor") => divisor = int_option(meta, &nv.lit)?,
+            _ => return Err(Error::new_spanned(meta, "unknown option for a CandlVertex"))
+        }
+        Ok(())
+    })?;
+    Ok(divisor)
+}
+
+/// check if the struct is `#[repr(C)]`, the order of the fields being
+/// otherwise unspecified
+fn is_repr_c(attrs: &[Attribute]) -> bool {
+    attrs.iter()
+        .filter(|attr| attr.path.is_ident("repr"))
+        .filter_map(|attr| match attr.parse_meta() {
+            Ok(Meta::List(list)) => Some(list),
+            _ => None
+        })
+        .any(|list| list.nested.iter().any(|nested| match nested {
+            NestedMeta::Meta(Meta::Path(path)) => path.is_ident("C"),
+            _ => false
+        }))
+}
+
+fn vertex_impl(input: &DeriveInput) -> Result<TokenStream2, Error> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => return Err(Error::new_spanned(input, "CandlVertex needs a struct with named fields"))
+        },
+        _ => return Err(Error::new_spanned(input, "CandlVertex can only be derived for a struct"))
+    };
+    if !is_repr_c(&input.attrs) {
+        return Err(Error::new(Span::call_site(), "CandlVertex needs a #[repr(C)] struct"));
+    }
+    let divisor = struct_divisor(&input.attrs)?;
+    let mut attributes = vec!();
+    for field in fields {
+        let options = field_options(&field.attrs)?;
+        if options.skip { continue; }
+        let ident = field.ident.as_ref().unwrap();
+        let ty = &field.ty;
+        let name = options.name.unwrap_or_else(|| ident.to_string());
+        let normalized = if options.normalized { quote! { .normalized() } } else { quote! {} };
+        let location = match options.location {
+            Some(location) => quote! { .set_location(#location) },
+            None => quote! {}
+        };
+        attributes.push(quote! {
+            .attribute_with(
+                ::candelabre_experiment::CandlVertexAttrib::new(
+                    #name,
+                    <#ty as ::candelabre_experiment::CandlAttribute>::format()#normalized,
+                    ::core::mem::offset_of!(Self, #ident)
+                )#location
+            )
+        });
+    }
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::candelabre_experiment::CandlVertex for #ident #ty_generics #where_clause {
+            fn layout() -> ::candelabre_experiment::CandlVertexLayout {
+                ::candelabre_experiment::CandlVertexLayout::of::<Self>()
+                    #(#attributes)*
+                    .set_divisor(#divisor)
+            }
+        }
+    })
+}
@@ -3,7 +3,7 @@
 
 use candelabre_windowing::{
     CandlCurrentWrapper, CandlDimension, CandlElement, CandlError,
-    CandlManager, CandlOptions, CandlWindow
+    CandlManager, CandlModeCriteria, CandlMonitor, CandlOptions, CandlWindow
 };
 use candelabre_windowing::glutin::event::{
     ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent
@@ -13,7 +13,6 @@ use candelabre_windowing::glutin::event_loop::{
 };
 use candelabre_windowing::glutin::dpi::PhysicalSize;
 use candelabre_windowing::glutin::monitor::VideoMode;
-use candelabre_windowing::glutin::window::WindowId;
 use luminance::context::GraphicsContext;
 use luminance::framebuffer::Framebuffer;
 use luminance::pipeline::PipelineState;
@@ -24,7 +23,6 @@ use luminance::tess::{Mode, Tess, TessBuilder};
 use luminance::texture::{Dim2, Flat};
 use luminance_derive::{Semantics, Vertex};
 use std::cell::RefCell;
-use std::collections::HashMap;
 use std::rc::Rc;
 
 // ============================================================================
@@ -40,6 +38,8 @@ unsafe impl GraphicsContext for LumSurface {
 }
 
 impl CandlWindow for LumSurface {
+    type Message = ();
+
     fn ctx(&mut self) -> CandlCurrentWrapper { self.ctx.take().unwrap() }
 
     fn ctx_ref(&self) -> &CandlCurrentWrapper { self.ctx.as_ref().unwrap() }
@@ -68,6 +68,9 @@ impl CandlElement<LumSurface> for LumSurface {
         options: CandlOptions
     ) -> Result<LumSurface, CandlError> {
         let ctx = LumSurface::init(el, video_mode, dim, title, options)?;
+        // luminance calls the global functions of the gl crate, loaded with
+        // the first context only, see utils.rs
+        GL_LOAD.call_once(|| gl::load_with(|s| ctx.get_proc_address(s) as *const _));
         let ctx = Some(CandlCurrentWrapper::PossiblyCurrent(ctx));
         //let gfx_state = Rc::new(RefCell::new(GraphicsState::new().unwrap()));
         let gfx_state = unsafe {
@@ -94,7 +97,7 @@ impl LumSurface {
 // structure to handle data for the window (Tess, triangles, etc)
 
 mod utils;
-use utils::{FS, VS};
+use utils::{FS, GL_LOAD, VS};
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Semantics)]
 pub enum Semantics {
@@ -144,26 +147,30 @@ impl LumData {
 fn main() {
     let el = EventLoop::new();
     let mut win_manager: CandlManager<LumSurface, ()> = CandlManager::new();
-    let mut win_datas = HashMap::<WindowId, LumData>::default();
     for idx in 0..3 {
         let wid = &win_manager.create_window::<_, LumSurface>(
             &el,
-            el.primary_monitor().video_modes().next().unwrap(),
+            CandlMonitor::primary(&el).select_mode(&CandlModeCriteria::default()).unwrap(),
             CandlDimension::Classic(800, 400),
             &format!("test luminance #{}", idx+1),
             CandlOptions::default()
         ).unwrap();
-        win_datas.insert(
-            wid.clone(),
-            LumData::new(win_manager.get_current(wid.clone()).unwrap())
-        );
+        let win_data = LumData::new(win_manager.get_current(wid.clone()).unwrap());
+        win_manager.set_user_data(wid.clone(), win_data);
     }
     el.run(move |evt, _, ctrl_flow| {
         match evt {
             Event::LoopDestroyed => return,
             Event::WindowEvent {event, window_id} => match event {
+                // the events can still come for a window already removed
                 WindowEvent::Resized(physical_size) =>
-                    win_manager.get_current(window_id).unwrap().resize(physical_size),
+                    if let Ok(surface) = win_manager.get_current(window_id) {
+                        surface.resize(physical_size);
+                    },
+                WindowEvent::ScaleFactorChanged { scale_factor, new_inner_size } =>
+                    if let Ok(surface) = win_manager.get_current(window_id) {
+                        surface.rescale(scale_factor, *new_inner_size);
+                    },
                 WindowEvent::CloseRequested
                 | WindowEvent::KeyboardInput {
                     input: KeyboardInput {
@@ -174,10 +181,9 @@ fn main() {
                 } => win_manager.remove_window(window_id),
                 _ => ()
             }
-            Event::RedrawRequested(win_id) => {
-                let surface = win_manager.get_current(win_id.clone()).unwrap();
+            Event::RedrawRequested(win_id) => if let Ok((surface, Some(win_data))) = win_manager
+                .get_current_with_data::<LumData>(win_id.clone()) {
                 let back_buffer = surface.back_buffer();
-                let win_data = win_datas.get(&win_id).unwrap();
                 surface.pipeline_builder().pipeline(
                     &back_buffer,
                     &PipelineState::default(),
@@ -7,108 +7,97 @@
 //! * 'C' to randomly change the color of the triangle of the current window
 
 use candelabre_windowing::{
-    CandlDimension, CandlManager, CandlOptions,
-    CandlRenderer, CandlWindow
+    CandlChord, CandlDimension, CandlKeymap, CandlManager, CandlModeCriteria,
+    CandlMonitor, CandlOptions, CandlRenderer, CandlTrigger, CandlWindow
 };
 use candelabre_windowing::glutin::event::{
-    ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent
+    Event, VirtualKeyCode, WindowEvent
 };
 use candelabre_windowing::glutin::event_loop::{
     ControlFlow, EventLoop, EventLoopWindowTarget
 };
-use candelabre_windowing::glutin::monitor::VideoMode;
 
 mod utils;
 use utils::{DemoSurface, SurfaceDrawer, SurfaceState, Message};
 
+fn keymap() -> CandlKeymap<Message> {
+    let mut keymap = CandlKeymap::new();
+    for (key, message) in vec![
+        (VirtualKeyCode::Escape, Message::Close),
+        (VirtualKeyCode::A, Message::NewWindow),
+        (VirtualKeyCode::Space, Message::RandomBgColor),
+        (VirtualKeyCode::C, Message::RandomTriangleColor)
+    ] {
+        let chord = CandlChord::key(key).set_trigger(CandlTrigger::Released);
+        keymap.bind(chord, message).unwrap();
+    }
+    keymap
+}
+
 fn add_win(
     manager: &mut CandlManager<DemoSurface, u32>,
     el: &EventLoopWindowTarget<()>,
-    video_mode: VideoMode,
+    monitor: CandlMonitor,
     title_nb: u32
 ) {
-    manager.create_window_with_state(
+    let win_id = manager.create_window_with_state(
         &el,
-        video_mode,
+        monitor.select_mode(&CandlModeCriteria::default()).unwrap(),
         CandlDimension::Classic(800, 400),
         &format!("multi window #{}", title_nb),
         CandlOptions::default(),
         SurfaceDrawer::init(),
         SurfaceState::default()
     ).unwrap();
+    manager.get_current(win_id).unwrap().set_keymap(keymap());
 }
 
 fn main() {
     let el = EventLoop::new();
-    let video_mode = el.primary_monitor().video_modes().next().unwrap();
     let mut win_manager = CandlManager::new_with_state(1);
 
     // first window
-    add_win(&mut win_manager, &el, video_mode, 0);
+    add_win(&mut win_manager, &el, CandlMonitor::primary(&el), 0);
 
     el.run(move |evt, el_wt, ctrl_flow| {
         match evt {
             Event::LoopDestroyed => return,
             Event::WindowEvent {event, window_id} => match event {
+                // the events can still come for a window already removed
                 WindowEvent::Resized(physical_size) =>
-                    win_manager.get_current(window_id).unwrap().resize(physical_size),
-                WindowEvent::CloseRequested
-                | WindowEvent::KeyboardInput {
-                    input: KeyboardInput {
-                        state: ElementState::Released,
-                        virtual_keycode: Some(VirtualKeyCode::Escape),
-                        ..
-                    }, ..
-                } => {
-                    win_manager.remove_window(window_id);
-                    if win_manager.is_empty() { *ctrl_flow = ControlFlow::Exit; }
-                }
-                WindowEvent::KeyboardInput {
-                    input: KeyboardInput {
-                        state: ElementState::Released,
-                        virtual_keycode: Some(keycode),
-                        ..
-                    }, ..
-                } => match keycode {
-                    VirtualKeyCode::Space => {
-                        let surface = win_manager.get_current(window_id).unwrap();
-                        surface.update(Message::RandomBgColor);
-                        surface.ask_redraw();
-                    }
-                    VirtualKeyCode::A => {
-                        let video_mode = win_manager
-                            .get_current(window_id)
-                            .unwrap()
-                            .get_window()
-                            .unwrap()
-                            .primary_monitor()
-                            .video_modes()
-                            .next()
-                            .unwrap();
-                        let nb = win_manager.state().clone();
-                        {
-                            let state = win_manager.state_mut();
-                            *state = *state + 1;
+                    if let Ok(surface) = win_manager.get_current(window_id) {
+                        surface.resize(physical_size);
+                    },
+                WindowEvent::ScaleFactorChanged { scale_factor, new_inner_size } =>
+                    if let Ok(surface) = win_manager.get_current(window_id) {
+                        surface.rescale(scale_factor, *new_inner_size);
+                    },
+                WindowEvent::CloseRequested => { win_manager.request_close(window_id); }
+                event => match win_manager.map_input(window_id, &event) {
+                    Some(Message::Close) => { win_manager.request_close(window_id); }
+                    Some(Message::NewWindow) => {
+                        let monitor = win_manager.get_current(window_id)
+                            .map(|surface| surface.current_monitor());
+                        if let Ok(monitor) = monitor {
+                            let nb = win_manager.state().clone();
+                            {
+                                let state = win_manager.state_mut();
+                                *state = *state + 1;
+                            }
+                            add_win(&mut win_manager, &el_wt, monitor, nb);
                         }
-                        add_win(&mut win_manager, &el_wt, video_mode.clone(), nb);
-                    }
-                    VirtualKeyCode::C => {
-                        let surface = win_manager.get_current(window_id).unwrap();
-                        surface.update(Message::RandomTriangleColor);
-                        surface.ask_redraw();
                     }
-                    _ => ()
+                    Some(message) => win_manager.update(window_id, message),
+                    None => ()
                 }
-                _ => ()
             }
             Event::MainEventsCleared => {
-                for wid in win_manager.list_window_ids() {
-                    let surface = win_manager.get_current(wid).unwrap();
-                    if surface.check_redraw() { surface.request_redraw(); }
+                for (wid, err) in win_manager.draw_dirty() {
+                    log::error!("Failed to draw the window {:?}: {}", wid, err);
                 }
             }
             Event::RedrawRequested(win_id) =>
-                win_manager.get_current(win_id).unwrap().draw(),
+                if let Ok(surface) = win_manager.get_current(win_id) { surface.draw(); },
             _ => ()
         }
         if win_manager.is_empty() { *ctrl_flow = ControlFlow::Exit }
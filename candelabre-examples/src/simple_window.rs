@@ -6,13 +6,10 @@
 //! change the name of the window.
 
 use candelabre_windowing::{
-    CandlDimension, CandlOptions, CandlRenderer,
-    CandlSurfaceBuilder, CandlWindow
-};
-use candelabre_windowing::glutin::event::{
-    ElementState, Event, KeyboardInput, StartCause,
-    VirtualKeyCode, WindowEvent
+    CandlCloseResponse, CandlDimension, CandlKeymap, CandlOptions,
+    CandlRenderer, CandlSurfaceBuilder, CandlWindow
 };
+use candelabre_windowing::glutin::event::{Event, StartCause, WindowEvent};
 use candelabre_windowing::glutin::event_loop::{ControlFlow, EventLoop};
 
 mod utils;
@@ -26,15 +23,18 @@ const TITLES_LIST: [&str; 5] = [
     "from OpenGL to candelabre ;-)"
 ];
 
+const KEYMAP: &str = include_str!("../resources/simple-keymap.txt");
+
 fn main() {
     let el = EventLoop::new();
+    let keymap = CandlKeymap::from_config(KEYMAP, Message::parse).unwrap();
     let mut surface: DemoSurface = CandlSurfaceBuilder::new()
         .dim(CandlDimension::Classic(800, 400))
         .title(TITLES_LIST[0])
         .options(CandlOptions::default())
         .render(SurfaceDrawer::init())
         .state(SurfaceState::default())
-        .video_mode(el.primary_monitor().video_modes().next().unwrap())
+        .keymap(keymap)
         .build(&el)
         .unwrap();
 
@@ -46,36 +46,26 @@ fn main() {
                 *ctrl_flow = ControlFlow::Wait,
             Event::WindowEvent { event, .. } => match event {
                 WindowEvent::Resized(physical_size) => surface.resize(physical_size),
-                WindowEvent::CloseRequested
-                | WindowEvent::KeyboardInput {
-                    input: KeyboardInput {
-                        state: ElementState::Released,
-                        virtual_keycode: Some(VirtualKeyCode::Escape),
-                        ..
-                    }, ..
-                } => *ctrl_flow = ControlFlow::Exit,
-                WindowEvent::KeyboardInput {
-                    input: KeyboardInput {
-                        state: ElementState::Released,
-                        virtual_keycode: Some(keycode),
-                        ..
-                    }, ..
-                } => match keycode {
-                    VirtualKeyCode::Space => {
-                        surface.update(Message::RandomBgColor);
-                        surface.ask_redraw();
+                WindowEvent::ScaleFactorChanged { scale_factor, new_inner_size } =>
+                    surface.rescale(scale_factor, *new_inner_size),
+                WindowEvent::CloseRequested => {
+                    if surface.request_close() == CandlCloseResponse::Confirm {
+                        *ctrl_flow = ControlFlow::Exit;
                     }
-                    VirtualKeyCode::A => {
+                }
+                event => match surface.map_input(&event) {
+                    Some(Message::Close) => {
+                        if surface.request_close() == CandlCloseResponse::Confirm {
+                            *ctrl_flow = ControlFlow::Exit;
+                        }
+                    }
+                    Some(Message::IncValue) => {
                         surface.update(Message::IncValue);
                         surface.title(TITLES_LIST[surface.state().get_value() as usize]);
                     }
-                    VirtualKeyCode::C => {
-                        surface.update(Message::RandomTriangleColor);
-                        surface.ask_redraw();
-                    }
-                    _ => ()
+                    Some(message) => surface.update(message),
+                    None => ()
                 }
-                _ => ()
             }
             Event::MainEventsCleared => {
                 if surface.check_redraw() { surface.request_redraw(); }
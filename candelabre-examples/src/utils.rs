@@ -1,14 +1,20 @@
-use candelabre_windowing::{CandlRenderer, CandlSurface, CandlUpdate};
-use gl;
+use candelabre_windowing::{
+    CandlContext, CandlError, CandlGl, CandlRenderer, CandlSurface, CandlUpdate
+};
+use candelabre_windowing::candl_gl;
 use nvg_gl::Renderer as NvgRenderer;
 use nvg::Context as NvgContext;
 use rand::Rng;
+use std::sync::Once;
 
 #[allow(dead_code)]
 pub const VS: &'static str = include_str!("../resources/simple-vs.glsl");
 #[allow(dead_code)]
 pub const FS: &'static str = include_str!("../resources/simple-fs.glsl");
 
+/// the global functions of the gl crate, used by nvg-gl, are loaded once
+pub static GL_LOAD: Once = Once::new();
+
 fn new_rf() -> f32 {
     (rand::thread_rng().gen_range(0, 101) as f32) / 100.0
 }
@@ -33,15 +39,33 @@ impl Default for SurfaceState {
 }
 
 #[allow(dead_code)]
+#[derive(Clone, Copy, Debug)]
 pub enum Message {
+    Close,
     IncValue,
+    NewWindow,
     RandomBgColor,
     RandomTriangleColor
 }
 
+#[allow(dead_code)]
+impl Message {
+    pub fn parse(name: &str) -> Option<Message> {
+        match name {
+            "close" => Some(Message::Close),
+            "inc_value" => Some(Message::IncValue),
+            "new_window" => Some(Message::NewWindow),
+            "random_bg_color" => Some(Message::RandomBgColor),
+            "random_triangle_color" => Some(Message::RandomTriangleColor),
+            _ => None
+        }
+    }
+}
+
 impl CandlUpdate<Message> for SurfaceState {
     fn update(&mut self, message: Message) {
         match message {
+            Message::Close | Message::NewWindow => (),
             Message::IncValue =>
                 self.value = if self.value == 4 { 0 } else { self.value+1 },
             Message::RandomBgColor =>
@@ -50,6 +74,14 @@ impl CandlUpdate<Message> for SurfaceState {
                 self.triangle_color = [new_ri(), new_ri(), new_ri()]
         }
     }
+
+    fn update_redraw(&mut self, message: Message) -> bool {
+        self.update(message);
+        match message {
+            Message::RandomBgColor | Message::RandomTriangleColor => true,
+            _ => false
+        }
+    }
 }
 
 #[allow(dead_code)]
@@ -63,6 +95,7 @@ impl SurfaceState {
 
 #[allow(dead_code)]
 pub struct SurfaceDrawer {
+    gl: Option<CandlGl>,
     context: Option<NvgContext<NvgRenderer>>,
     size: (u32, u32),
     factor: f64
@@ -71,16 +104,31 @@ pub struct SurfaceDrawer {
 impl CandlRenderer<SurfaceDrawer, SurfaceState, Message> for SurfaceDrawer {
     fn init() -> Self {
         Self {
+            gl: None,
             context: None,
             size: (0, 0),
             factor: 0.0
         }
     }
 
-    fn finalize(&mut self) {
-        let renderer = NvgRenderer::create().unwrap();
-        let context = NvgContext::create(renderer).unwrap();
+    fn setup(&mut self, ctx: &CandlContext) -> Result<(), CandlError> {
+        // nvg-gl calls the global functions of the gl crate, so they're loaded
+        // with the first context only, and used for all the windows: this
+        // only works while all the contexts share the same driver and pixel
+        // format, the per-context functions are in ctx.gl()
+        GL_LOAD.call_once(|| gl::load_with(|s| ctx.gl().get_proc_address(s)));
+        let renderer = NvgRenderer::create()
+            .map_err(|e| CandlError::RendererError(e.to_string()))?;
+        let context = NvgContext::create(renderer)
+            .map_err(|e| CandlError::RendererError(e.to_string()))?;
+        self.gl = Some(ctx.gl().clone());
         self.context = Some(context);
+        Ok(())
+    }
+
+    fn teardown(&mut self, _ctx: &CandlContext) {
+        self.context = None;
+        self.gl = None;
     }
 
     fn set_scale_factor(&mut self, scale_factor: f64) { self.factor = scale_factor; }
@@ -88,25 +136,30 @@ impl CandlRenderer<SurfaceDrawer, SurfaceState, Message> for SurfaceDrawer {
     fn set_size(&mut self, size: (u32, u32)) {
         self.size = size;
         let (w, h) = size;
-        unsafe { gl::Viewport(0, 0, w as i32, h as i32); }
+        if let Some(gl) = &self.gl {
+            unsafe { gl.Viewport(0, 0, w as i32, h as i32); }
+        }
     }
 
     fn draw_frame(&mut self, state: &SurfaceState) {
         let bg_col = state.get_bg_color();
         let tr_col = state.get_triangle_color();
         let (w, h) = self.size;
-        unsafe {
-            gl::ClearColor(bg_col[0], bg_col[1], bg_col[2], 1.0);
-            gl::Clear(
-                gl::COLOR_BUFFER_BIT |
-                gl::DEPTH_BUFFER_BIT |
-                gl::STENCIL_BUFFER_BIT
-            );
+        let factor = self.factor as f32;
+        if let Some(gl) = &self.gl {
+            unsafe {
+                gl.ClearColor(bg_col[0], bg_col[1], bg_col[2], 1.0);
+                gl.Clear(
+                    candl_gl::COLOR_BUFFER_BIT |
+                    candl_gl::DEPTH_BUFFER_BIT |
+                    candl_gl::STENCIL_BUFFER_BIT
+                );
+            }
         }
         if let Some(ctxt) = &mut self.context {
             ctxt.begin_frame(
-                nvg::Extent::new(w as f32, h as f32),
-                self.factor as f32
+                nvg::Extent::new(w as f32 / factor, h as f32 / factor),
+                factor
             ).unwrap();
             ctxt.save();
             ctxt.begin_path();
@@ -0,0 +1,669 @@
+//! Buffers, vertex arrays and draw calls
+//!
+//! The geometry is uploaded in buffer objects, then a vertex array binds the
+//! attributes of a program to the vertex buffers, following their layouts,
+//! with an optional index buffer. The attributes are found by name in the
+//! reflection of the program, and checked against their GLSL types.
+
+use super::{CandlGlslType, CandlGraphicsError, CandlProgram};
+use candelabre_windowing::candl_gl::{self as gl, CandlGl};
+use candelabre_windowing::candl_gl::types::{GLenum, GLint, GLsizei, GLsizeiptr, GLuint};
+use std::mem::{size_of, size_of_val};
+use std::os::raw::c_void;
+
+/// how often the data of a buffer are expected to change
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum CandlBufferUsage {
+    /// uploaded once, drawn many times
+    Static,
+    /// updated from time to time, drawn many times
+    Dynamic,
+    /// updated every frame
+    Stream
+}
+
+impl CandlBufferUsage {
+    fn get_glenum(&self) -> GLenum {
+        match self {
+            Self::Static => gl::STATIC_DRAW,
+            Self::Dynamic => gl::DYNAMIC_DRAW,
+            Self::Stream => gl::STREAM_DRAW
+        }
+    }
+}
+
+/// type of the indices of an index buffer
+pub trait CandlIndex: Copy {
+    /// the OpenGL type of the index
+    fn get_glenum() -> GLenum;
+}
+
+impl CandlIndex for u8 {
+    fn get_glenum() -> GLenum { gl::UNSIGNED_BYTE }
+}
+
+impl CandlIndex for u16 {
+    fn get_glenum() -> GLenum { gl::UNSIGNED_SHORT }
+}
+
+impl CandlIndex for u32 {
+    fn get_glenum() -> GLenum { gl::UNSIGNED_INT }
+}
+
+/// candelabre buffer
+///
+/// A buffer object holding vertices, or indices. The context must be current
+/// to create, update or delete it.
+#[derive(Debug)]
+pub struct CandlBuffer {
+    gl: CandlGl,
+    ptr: GLuint,
+    usage: CandlBufferUsage,
+    index_type: Option<(GLenum, usize)>,
+    size: usize
+}
+
+impl CandlBuffer {
+    /// create a vertex buffer, with its data
+    pub fn vertex<T: Copy>(gl: &CandlGl, usage: CandlBufferUsage, data: &[T]) -> Self {
+        CandlBuffer::create(gl, usage, None, data)
+    }
+
+    /// create an index buffer, with its indices
+    pub fn index<I: CandlIndex>(gl: &CandlGl, usage: CandlBufferUsage, indices: &[I]) -> Self {
+        CandlBuffer::create(gl, usage, Some((I::get_glenum(), size_of::<I>())), indices)
+    }
+
+    fn create<T: Copy>(
+        gl: &CandlGl,
+        usage: CandlBufferUsage,
+        index_type: Option<(GLenum, usize)>,
+        data: &[T]
+    ) -> Self {
+        let mut ptr = 0;
+        unsafe { gl.GenBuffers(1, &mut ptr); }
+        let mut buffer = CandlBuffer { gl: gl.clone(), ptr, usage, index_type, size: 0 };
+        unsafe { buffer.upload(data); }
+        buffer
+    }
+
+    /// bind the buffer to its target, without changing the vertex array in
+    /// use for the index buffers
+    unsafe fn bind(&self) -> GLenum {
+        let target = self.target();
+        if target == gl::ELEMENT_ARRAY_BUFFER { self.gl.BindVertexArray(0); }
+        self.gl.BindBuffer(target, self.ptr);
+        target
+    }
+
+    unsafe fn upload<T: Copy>(&mut self, data: &[T]) {
+        let target = self.bind();
+        self.size = size_of_val(data);
+        self.gl.BufferData(
+            target,
+            self.size as GLsizeiptr,
+            data.as_ptr() as *const c_void,
+            self.usage.get_glenum()
+        );
+        self.gl.BindBuffer(target, 0);
+    }
+
+    /// check if the data can go in this buffer
+    fn check_data<T>(&self) -> Result<(), CandlGraphicsError> {
+        match self.index_type {
+            Some((_, index_size)) if index_size != size_of::<T>() => Err(
+                CandlGraphicsError::BufferError(String::from("the indices aren't of the type of the buffer"))
+            ),
+            _ => Ok(())
+        }
+    }
+
+    /// replace all the data of the buffer, which can change its size
+    pub fn set_data<T: Copy>(&mut self, data: &[T]) -> Result<(), CandlGraphicsError> {
+        self.check_data::<T>()?;
+        unsafe { self.upload(data); }
+        Ok(())
+    }
+
+    /// replace a part of the data, from an offset in bytes
+    pub fn update<T: Copy>(&mut self, offset: usize, data: &[T]) -> Result<(), CandlGraphicsError> {
+        self.check_data::<T>()?;
+        let len = size_of_val(data);
+        if offset.checked_add(len).map_or(true, |end| end > self.size) {
+            return Err(CandlGraphicsError::BufferError(
+                format!("{} bytes at offset {} overflow a buffer of {} bytes", len, offset, self.size)
+            ));
+        }
+        unsafe {
+            let target = self.bind();
+            self.gl.BufferSubData(target, offset as isize, len as GLsizeiptr, data.as_ptr() as *const c_void);
+            self.gl.BindBuffer(target, 0);
+        }
+        Ok(())
+    }
+
+    /// get the GL pointer to the buffer
+    pub fn get_ptr(&self) -> GLuint { self.ptr }
+
+    /// get the target of the buffer (`GL_ARRAY_BUFFER` or
+    /// `GL_ELEMENT_ARRAY_BUFFER`)
+    pub fn target(&self) -> GLenum {
+        if self.index_type.is_some() { gl::ELEMENT_ARRAY_BUFFER } else { gl::ARRAY_BUFFER }
+    }
+
+    /// get the usage of the buffer
+    pub fn usage(&self) -> CandlBufferUsage { self.usage }
+
+    /// get the size of the buffer, in bytes
+    pub fn size(&self) -> usize { self.size }
+
+    /// get the number of indices, for an index buffer
+    pub fn index_count(&self) -> Option<usize> {
+        self.index_type.map(|(_, index_size)| self.size / index_size)
+    }
+
+    /// delete the buffer
+    pub fn delete(self) {
+        unsafe { self.gl.DeleteBuffers(1, &self.ptr); }
+    }
+}
+
+/// type of the components of an attribute in a vertex buffer
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum CandlComponent {
+    /// `f32`
+    F32,
+    /// `i8`
+    I8,
+    /// `u8`
+    U8,
+    /// `i16`
+    I16,
+    /// `u16`
+    U16,
+    /// `i32`
+    I32,
+    /// `u32`
+    U32
+}
+
+impl CandlComponent {
+    fn get_glenum(&self) -> GLenum {
+        match self {
+            Self::F32 => gl::FLOAT,
+            Self::I8 => gl::BYTE,
+            Self::U8 => gl::UNSIGNED_BYTE,
+            Self::I16 => gl::SHORT,
+            Self::U16 => gl::UNSIGNED_SHORT,
+            Self::I32 => gl::INT,
+            Self::U32 => gl::UNSIGNED_INT
+        }
+    }
+
+    /// check if the component is an integer
+    pub fn is_integer(&self) -> bool { *self != Self::F32 }
+}
+
+/// format of an attribute in a vertex buffer
+///
+/// The integers are given as is to the integer attributes (`int`, `uvec2`,
+/// etc), or converted to floats for the float attributes, in [0, 1] or
+/// [-1, 1] if normalized.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct CandlAttribFormat {
+    component: CandlComponent,
+    count: u8,
+    normalized: bool
+}
+
+impl CandlAttribFormat {
+    /// create a format, with the number of components (1 to 4)
+    pub fn new(component: CandlComponent, count: u8) -> Result<Self, CandlGraphicsError> {
+        if !(1..=4).contains(&count) {
+            return Err(CandlGraphicsError::BufferError(
+                format!("an attribute has 1 to 4 components, not {}", count)
+            ));
+        }
+        Ok(CandlAttribFormat { component, count, normalized: false })
+    }
+
+    /// normalize the integer components
+    pub fn normalized(mut self) -> Self {
+        self.normalized = self.component.is_integer();
+        self
+    }
+
+    /// get the type of the components
+    pub fn component(&self) -> CandlComponent { self.component }
+
+    /// get the number of components
+    pub fn count(&self) -> u8 { self.count }
+
+    /// check if the components are normalized
+    pub fn is_normalized(&self) -> bool { self.normalized }
+
+    /// check if the format can feed an attribute of a GLSL type, and if it
+    /// goes as integers
+    fn check(&self, kind: CandlGlslType) -> Option<bool> {
+        let (count, integer) = match kind {
+            CandlGlslType::Float => (1, false),
+            CandlGlslType::Vec2 => (2, false),
+            CandlGlslType::Vec3 => (3, false),
+            CandlGlslType::Vec4 => (4, false),
+            CandlGlslType::Int | CandlGlslType::UInt => (1, true),
+            CandlGlslType::IVec2 | CandlGlslType::UVec2 => (2, true),
+            CandlGlslType::IVec3 | CandlGlslType::UVec3 => (3, true),
+            CandlGlslType::IVec4 | CandlGlslType::UVec4 => (4, true),
+            _ => return None
+        };
+        let integer_data = self.component.is_integer() && !self.normalized;
+        if self.count > count || (integer && !integer_data) { None } else { Some(integer) }
+    }
+}
+
+/// type which can be used as an attribute in a vertex buffer
+pub trait CandlAttribute {
+    /// get the format of the attribute
+    fn format() -> CandlAttribFormat;
+}
+
+macro_rules! impl_attribute {
+    ($($ty:ty => $component:ident, $count:expr);+ $(;)?) => {
+        $(
+            impl CandlAttribute for $ty {
+                fn format() -> CandlAttribFormat {
+                    CandlAttribFormat { component: CandlComponent::$component, count: $count, normalized: false }
+                }
+            }
+        )+
+    };
+}
+
+impl_attribute!(
+    f32 => F32, 1; [f32; 2] => F32, 2; [f32; 3] => F32, 3; [f32; 4] => F32, 4;
+    i8 => I8, 1; [i8; 2] => I8, 2; [i8; 3] => I8, 3; [i8; 4] => I8, 4;
+    u8 => U8, 1; [u8; 2] => U8, 2; [u8; 3] => U8, 3; [u8; 4] => U8, 4;
+    i16 => I16, 1; [i16; 2] => I16, 2; [i16; 3] => I16, 3; [i16; 4] => I16, 4;
+    u16 => U16, 1; [u16; 2] => U16, 2; [u16; 3] => U16, 3; [u16; 4] => U16, 4;
+    i32 => I32, 1; [i32; 2] => I32, 2; [i32; 3] => I32, 3; [i32; 4] => I32, 4;
+    u32 => U32, 1; [u32; 2] => U32, 2; [u32; 3] => U32, 3; [u32; 4] => U32, 4
+);
+
+/// attribute of a vertex layout
+///
+/// The attribute is bound to the input of the vertex shader with the same
+/// name, or to a location if it's set.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct CandlVertexAttrib {
+    name: String,
+    format: CandlAttribFormat,
+    offset: usize,
+    location: Option<GLuint>
+}
+
+impl CandlVertexAttrib {
+    /// create an attribute, at an offset in bytes
+    pub fn new<N: Into<String>>(name: N, format: CandlAttribFormat, offset: usize) -> Self {
+        CandlVertexAttrib { name: name.into(), format, offset, location: None }
+    }
+
+    /// bind the attribute to a location instead of its name
+    pub fn set_location(mut self, location: GLuint) -> Self {
+        self.location = Some(location);
+        self
+    }
+
+    /// get the name of the attribute in the shaders
+    pub fn name(&self) -> &str { &self.name }
+
+    /// get the format of the attribute
+    pub fn format(&self) -> CandlAttribFormat { self.format }
+
+    /// get the offset of the attribute in a vertex, in bytes
+    pub fn offset(&self) -> usize { self.offset }
+
+    /// get the location of the attribute, if it's set
+    pub fn location(&self) -> Option<GLuint> { self.location }
+}
+
+/// vertex type with a known layout
+///
+/// Derive it with `#[derive(CandlVertex)]` on a `#[repr(C)]` struct, see the
+/// `candelabre-derive` crate for the options of the fields.
+pub trait CandlVertex: Copy {
+    /// get the layout of the vertices of this type
+    fn layout() -> CandlVertexLayout;
+}
+
+/// layout of the vertices in a vertex buffer
+///
+/// The attributes are named after the inputs of the vertex shader. With a
+/// divisor, the attributes advance once per instance (or per group of
+/// instances) instead of once per vertex.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct CandlVertexLayout {
+    stride: usize,
+    divisor: u32,
+    attributes: Vec<CandlVertexAttrib>
+}
+
+impl CandlVertexLayout {
+    /// create an empty layout, with the size of a vertex in bytes
+    pub fn new(stride: usize) -> Self {
+        CandlVertexLayout { stride, divisor: 0, attributes: vec!() }
+    }
+
+    /// create an empty layout for a vertex type
+    pub fn of<V>() -> Self { CandlVertexLayout::new(size_of::<V>()) }
+
+    /// add an attribute, at an offset in bytes
+    pub fn attribute<A: CandlAttribute, N: Into<String>>(self, name: N, offset: usize) -> Self {
+        self.attribute_format(name, A::format(), offset)
+    }
+
+    /// add an attribute with normalized integer components
+    pub fn normalized_attribute<A, N>(self, name: N, offset: usize) -> Self
+    where A: CandlAttribute, N: Into<String> {
+        self.attribute_format(name, A::format().normalized(), offset)
+    }
+
+    /// add an attribute with any format
+    pub fn attribute_format<N: Into<String>>(self, name: N, format: CandlAttribFormat, offset: usize)
+    -> Self {
+        self.attribute_with(CandlVertexAttrib::new(name, format, offset))
+    }
+
+    /// add an attribute already built
+    pub fn attribute_with(mut self, attrib: CandlVertexAttrib) -> Self {
+        self.attributes.push(attrib);
+        self
+    }
+
+    /// advance the attributes once per `divisor` instances, 0 to advance
+    /// them once per vertex
+    pub fn set_divisor(mut self, divisor: u32) -> Self {
+        self.divisor = divisor;
+        self
+    }
+
+    /// get the size of a vertex, in bytes
+    pub fn stride(&self) -> usize { self.stride }
+
+    /// get the divisor
+    pub fn divisor(&self) -> u32 { self.divisor }
+
+    /// get the attributes
+    pub fn attributes(&self) -> &[CandlVertexAttrib] { &self.attributes }
+}
+
+/// kind of primitive to draw
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum CandlPrimitive {
+    /// points
+    Points,
+    /// lines
+    Lines,
+    /// line strip
+    LineStrip,
+    /// line loop
+    LineLoop,
+    /// triangles
+    Triangles,
+    /// triangle strip
+    TriangleStrip,
+    /// triangle fan
+    TriangleFan,
+    /// patches for the tessellation shaders, with their number of vertices
+    Patches(u8)
+}
+
+impl CandlPrimitive {
+    fn get_glenum(&self) -> GLenum {
+        match self {
+            Self::Points => gl::POINTS,
+            Self::Lines => gl::LINES,
+            Self::LineStrip => gl::LINE_STRIP,
+            Self::LineLoop => gl::LINE_LOOP,
+            Self::Triangles => gl::TRIANGLES,
+            Self::TriangleStrip => gl::TRIANGLE_STRIP,
+            Self::TriangleFan => gl::TRIANGLE_FAN,
+            Self::Patches(_) => gl::PATCHES
+        }
+    }
+}
+
+/// candelabre vertex array
+///
+/// Bind the attributes of a program to vertex buffers, with an optional index
+/// buffer. The locations come from the program given at creation, so another
+/// program can only be used with it if its attributes have the same
+/// locations (with `layout(location = N)` in the shaders for example).
+///
+/// The numbers of vertices and indices are the ones of the buffers at
+/// creation, set them again after resizing the buffers.
+#[derive(Debug)]
+pub struct CandlVertexArray {
+    gl: CandlGl,
+    ptr: GLuint,
+    vertex_count: usize,
+    index: Option<(GLenum, usize, usize)>
+}
+
+impl CandlVertexArray {
+    /// create a vertex array, from the buffers with their layouts
+    ///
+    /// The attributes of the layouts missing from the program are ignored,
+    /// as the driver removes the unused ones.
+    pub fn new(
+        gl: &CandlGl,
+        program: &CandlProgram,
+        buffers: &[(&CandlBuffer, &CandlVertexLayout)],
+        index: Option<&CandlBuffer>
+    ) -> Result<CandlVertexArray, CandlGraphicsError> {
+        let error = |message: String| Err(CandlGraphicsError::BufferError(message));
+        let mut bindings = vec!();
+        for (buffer, layout) in buffers {
+            if buffer.index_type.is_some() {
+                return error(String::from("an index buffer is used as a vertex buffer"));
+            }
+            for attrib in layout.attributes() {
+                let active = match attrib.location {
+                    Some(location) => program.attributes().iter()
+                        .find(|active| active.location() == location as GLint),
+                    None => program.get_attribute(attrib.name())
+                };
+                let active = match (active, attrib.location) {
+                    (Some(active), _) => active,
+                    (None, Some(location)) => {
+                        // not reflected, the shaders are trusted with the type
+                        let integer = attrib.format.component.is_integer() && !attrib.format.normalized;
+                        bindings.push(
+                            (buffer.ptr, layout.stride, layout.divisor, attrib, location as GLint, integer)
+                        );
+                        continue;
+                    }
+                    (None, None) => continue
+                };
+                match attrib.format.check(active.kind()) {
+                    Some(integer) => bindings.push(
+                        (buffer.ptr, layout.stride, layout.divisor, attrib, active.location(), integer)
+                    ),
+                    None => return error(format!(
+                        "the attribute \"{}\" is a {}, it can't take {} {:?} components{}",
+                        attrib.name(), active.kind(), attrib.format.count, attrib.format.component,
+                        if attrib.format.normalized { " normalized" } else { "" }
+                    ))
+                }
+            }
+        }
+        let index = match index {
+            Some(buffer) => match buffer.index_type {
+                Some((index_type, index_size)) => Some((buffer.ptr, index_type, index_size, buffer.size / index_size)),
+                None => return error(String::from("a vertex buffer is used as an index buffer"))
+            },
+            None => None
+        };
+        let vertex_count = buffers.iter()
+            .filter(|(_, layout)| layout.divisor == 0 && layout.stride > 0)
+            .map(|(buffer, layout)| buffer.size / layout.stride)
+            .min()
+            .unwrap_or(0);
+        let mut ptr = 0;
+        unsafe {
+            gl.GenVertexArrays(1, &mut ptr);
+            gl.BindVertexArray(ptr);
+            for (buffer, stride, divisor, attrib, location, integer) in bindings {
+                let location = location as GLuint;
+                let format = attrib.format;
+                let offset = attrib.offset as *const c_void;
+                gl.BindBuffer(gl::ARRAY_BUFFER, buffer);
+                gl.EnableVertexAttribArray(location);
+                if integer {
+                    gl.VertexAttribIPointer(location, format.count as GLint,
+                        format.component.get_glenum(), stride as GLsizei, offset);
+                } else {
+                    gl.VertexAttribPointer(location, format.count as GLint,
+                        format.component.get_glenum(), format.normalized as u8,
+                        stride as GLsizei, offset);
+                }
+                if divisor > 0 { gl.VertexAttribDivisor(location, divisor); }
+            }
+            if let Some((buffer, _, _, _)) = index {
+                gl.BindBuffer(gl::ELEMENT_ARRAY_BUFFER, buffer);
+            }
+            gl.BindVertexArray(0);
+            gl.BindBuffer(gl::ARRAY_BUFFER, 0);
+        }
+        Ok(CandlVertexArray {
+            gl: gl.clone(),
+            ptr,
+            vertex_count,
+            index: index.map(|(_, index_type, index_size, count)| (index_type, index_size, count))
+        })
+    }
+
+    /// get the GL pointer to the vertex array
+    pub fn get_ptr(&self) -> GLuint { self.ptr }
+
+    /// get the number of vertices drawn without index buffer
+    pub fn vertex_count(&self) -> usize { self.vertex_count }
+
+    /// set the number of vertices drawn without index buffer
+    pub fn set_vertex_count(&mut self, count: usize) { self.vertex_count = count; }
+
+    /// get the number of indices, if there is an index buffer
+    pub fn index_count(&self) -> Option<usize> { self.index.map(|(_, _, count)| count) }
+
+    /// set the number of indices, if there is an index buffer
+    pub fn set_index_count(&mut self, count: usize) {
+        if let Some(index) = &mut self.index { index.2 = count; }
+    }
+
+    /// draw all the vertices (or indices) with a program
+    pub fn draw(&self, program: &CandlProgram, primitive: CandlPrimitive) {
+        let count = self.index_count().unwrap_or(self.vertex_count);
+        unsafe { self.draw_unchecked(program, primitive, 0, count, 1); }
+    }
+
+    /// draw a range of the vertices (or indices) with a program
+    pub fn draw_range(&self, program: &CandlProgram, primitive: CandlPrimitive, first: usize, count: usize)
+    -> Result<(), CandlGraphicsError> {
+        let total = self.index_count().unwrap_or(self.vertex_count);
+        if first.checked_add(count).map_or(true, |end| end > total) {
+            return Err(CandlGraphicsError::BufferError(
+                format!("{} elements from {} overflow the {} elements", count, first, total)
+            ));
+        }
+        unsafe { self.draw_unchecked(program, primitive, first, count, 1); }
+        Ok(())
+    }
+
+    /// draw several instances of all the vertices (or indices)
+    pub fn draw_instanced(&self, program: &CandlProgram, primitive: CandlPrimitive, instances: usize) {
+        let count = self.index_count().unwrap_or(self.vertex_count);
+        unsafe { self.draw_unchecked(program, primitive, 0, count, instances); }
+    }
+
+    unsafe fn draw_unchecked(
+        &self,
+        program: &CandlProgram,
+        primitive: CandlPrimitive,
+        first: usize,
+        count: usize,
+        instances: usize
+    ) {
+        self.gl.UseProgram(program.get_ptr());
+        self.gl.BindVertexArray(self.ptr);
+        if let CandlPrimitive::Patches(vertices) = primitive {
+            self.gl.PatchParameteri(gl::PATCH_VERTICES, vertices as GLint);
+        }
+        let mode = primitive.get_glenum();
+        match self.index {
+            Some((index_type, index_size, _)) => {
+                let offset = (first * index_size) as *const c_void;
+                if instances == 1 {
+                    self.gl.DrawElements(mode, count as GLsizei, index_type, offset);
+                } else {
+                    self.gl.DrawElementsInstanced(mode, count as GLsizei, index_type, offset,
+                        instances as GLsizei);
+                }
+            }
+            None => {
+                if instances == 1 {
+                    self.gl.DrawArrays(mode, first as GLint, count as GLsizei);
+                } else {
+                    self.gl.DrawArraysInstanced(mode, first as GLint, count as GLsizei,
+                        instances as GLsizei);
+                }
+            }
+        }
+        self.gl.BindVertexArray(0);
+    }
+
+    /// delete the vertex array, the buffers are kept
+    pub fn delete(self) {
+        unsafe { self.gl.DeleteVertexArrays(1, &self.ptr); }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn feed_the_float_attributes() {
+        let format = f32::format();
+        assert_eq!(CandlAttribFormat::new(CandlComponent::F32, 1).ok(), Some(format));
+        let format = <[f32; 3]>::format();
+        assert_eq!(format.check(CandlGlslType::Vec3), Some(false));
+        assert_eq!(format.check(CandlGlslType::Vec4), Some(false));
+        assert_eq!(format.check(CandlGlslType::Vec2), None);
+        assert_eq!(format.check(CandlGlslType::IVec3), None);
+        let format = <[u8; 4]>::format().normalized();
+        assert!(format.is_normalized());
+        assert_eq!(format.check(CandlGlslType::Vec4), Some(false));
+        assert_eq!(format.check(CandlGlslType::UVec4), None);
+    }
+
+    #[test]
+    fn feed_the_integer_attributes() {
+        let format = <[i16; 2]>::format();
+        assert_eq!(format.check(CandlGlslType::IVec2), Some(true));
+        assert_eq!(format.check(CandlGlslType::UVec3), Some(true));
+        assert_eq!(format.check(CandlGlslType::Vec2), Some(false));
+        assert_eq!(format.check(CandlGlslType::Int), None);
+        assert_eq!(format.check(CandlGlslType::Mat2), None);
+        assert!(!f32::format().normalized().is_normalized());
+    }
+
+    #[test]
+    fn reject_the_bad_component_counts() {
+        for count in [0, 5] {
+            match CandlAttribFormat::new(CandlComponent::F32, count) {
+                Err(CandlGraphicsError::BufferError(message)) =>
+                    assert_eq!(message, format!("an attribute has 1 to 4 components, not {}", count)),
+                res => panic!("expected a buffer error, got {:?}", res)
+            }
+        }
+    }
+}
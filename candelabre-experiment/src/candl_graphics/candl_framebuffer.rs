@@ -0,0 +1,264 @@
+//! Render targets
+//!
+//! A render target is a framebuffer object with color attachments, and an
+//! optional depth (and stencil) attachment, all textures which can be sampled
+//! once the rendering is done. With multisampling, the rendering goes into
+//! multisampled renderbuffers, resolved into the textures afterwards.
+
+use super::{CandlGraphicsError, CandlTexture, CandlTextureFormat, CandlTextureKind};
+use candelabre_windowing::candl_gl::{self as gl, CandlGl};
+use candelabre_windowing::candl_gl::types::{GLenum, GLint, GLsizei, GLuint};
+
+/// the multisampled framebuffer of a render target
+#[derive(Debug)]
+struct CandlMultisample {
+    fbo: GLuint,
+    renderbuffers: Vec<GLuint>
+}
+
+/// candelabre render target
+///
+/// Bind it to render into its attachments instead of the window, then
+/// `resolve()` it to use its textures. The context must be current to
+/// create, bind, resolve or delete it.
+#[derive(Debug)]
+pub struct CandlRenderTarget {
+    gl: CandlGl,
+    fbo: GLuint,
+    size: (u32, u32),
+    samples: u32,
+    colors: Vec<CandlTexture>,
+    depth: Option<CandlTexture>,
+    msaa: Option<CandlMultisample>
+}
+
+impl CandlRenderTarget {
+    /// create a render target, with the formats of its color attachments,
+    /// of its depth attachment (`Depth24Stencil8` for a stencil too), and
+    /// its number of samples (0 or 1 without multisampling)
+    pub fn new(
+        gl: &CandlGl,
+        size: (u32, u32),
+        colors: &[CandlTextureFormat],
+        depth: Option<CandlTextureFormat>,
+        samples: u32
+    ) -> Result<CandlRenderTarget, CandlGraphicsError> {
+        if colors.iter().any(|format| format.is_depth()) {
+            return Err(CandlGraphicsError::FramebufferError(String::from("a color attachment has a depth format")));
+        }
+        if depth.filter(|format| !format.is_depth()).is_some() {
+            return Err(CandlGraphicsError::FramebufferError(String::from("the depth attachment has a color format")));
+        }
+        let samples = if samples > 1 {
+            let mut max_samples: GLint = 0;
+            unsafe { gl.GetIntegerv(gl::MAX_SAMPLES, &mut max_samples); }
+            samples.min(max_samples.max(1) as u32)
+        } else { 0 };
+        let mut target = CandlRenderTarget {
+            gl: gl.clone(),
+            fbo: 0,
+            size,
+            samples,
+            colors: Vec::with_capacity(colors.len()),
+            depth: None,
+            msaa: None
+        };
+        // from here, the target is deleted on error, with what it holds
+        if let Err(err) = target.create(colors, depth) {
+            target.delete();
+            return Err(err);
+        }
+        Ok(target)
+    }
+
+    fn create(&mut self, colors: &[CandlTextureFormat], depth: Option<CandlTextureFormat>)
+    -> Result<(), CandlGraphicsError> {
+        for format in colors {
+            self.colors.push(CandlTexture::new::<u8>(&self.gl, CandlTextureKind::Texture2D, *format, self.size, None)?);
+        }
+        if let Some(format) = depth {
+            self.depth = Some(CandlTexture::new::<u8>(&self.gl, CandlTextureKind::Texture2D, format, self.size, None)?);
+        }
+        unsafe {
+            self.gl.GenFramebuffers(1, &mut self.fbo);
+            self.gl.BindFramebuffer(gl::FRAMEBUFFER, self.fbo);
+            for (idx, texture) in self.colors.iter().enumerate() {
+                self.gl.FramebufferTexture2D(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0 + idx as GLenum,
+                    gl::TEXTURE_2D, texture.get_ptr(), 0);
+            }
+            if let Some(texture) = &self.depth {
+                self.gl.FramebufferTexture2D(gl::FRAMEBUFFER, depth_attachment(texture.format()),
+                    gl::TEXTURE_2D, texture.get_ptr(), 0);
+            }
+            self.set_draw_buffers();
+            check_status(&self.gl)?;
+            if self.samples > 1 {
+                let mut msaa = CandlMultisample { fbo: 0, renderbuffers: vec!() };
+                self.gl.GenFramebuffers(1, &mut msaa.fbo);
+                self.gl.BindFramebuffer(gl::FRAMEBUFFER, msaa.fbo);
+                let attachments = colors.iter().enumerate()
+                    .map(|(idx, format)| (gl::COLOR_ATTACHMENT0 + idx as GLenum, *format))
+                    .chain(depth.map(|format| (depth_attachment(format), format)));
+                for (attachment, format) in attachments {
+                    let mut renderbuffer = 0;
+                    self.gl.GenRenderbuffers(1, &mut renderbuffer);
+                    msaa.renderbuffers.push(renderbuffer);
+                    self.gl.BindRenderbuffer(gl::RENDERBUFFER, renderbuffer);
+                    self.gl.RenderbufferStorageMultisample(gl::RENDERBUFFER, self.samples as GLsizei,
+                        format.get_glenums().0, self.size.0 as GLsizei, self.size.1 as GLsizei);
+                    self.gl.FramebufferRenderbuffer(gl::FRAMEBUFFER, attachment, gl::RENDERBUFFER, renderbuffer);
+                }
+                self.gl.BindRenderbuffer(gl::RENDERBUFFER, 0);
+                self.msaa = Some(msaa);
+                self.set_draw_buffers();
+                check_status(&self.gl)?;
+            }
+            self.gl.BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+        Ok(())
+    }
+
+    /// enable all the color attachments of the bound framebuffer
+    unsafe fn set_draw_buffers(&self) {
+        if self.colors.is_empty() {
+            self.gl.DrawBuffer(gl::NONE);
+            self.gl.ReadBuffer(gl::NONE);
+        } else {
+            let buffers: Vec<GLenum> = (0..self.colors.len())
+                .map(|idx| gl::COLOR_ATTACHMENT0 + idx as GLenum)
+                .collect();
+            self.gl.DrawBuffers(buffers.len() as GLsizei, buffers.as_ptr());
+        }
+    }
+
+    /// bind the target, to render into it, and set the viewport to its size
+    pub fn bind(&self) {
+        let fbo = self.msaa.as_ref().map(|msaa| msaa.fbo).unwrap_or(self.fbo);
+        unsafe {
+            self.gl.BindFramebuffer(gl::FRAMEBUFFER, fbo);
+            self.gl.Viewport(0, 0, self.size.0 as GLsizei, self.size.1 as GLsizei);
+        }
+    }
+
+    /// finish the rendering: resolve the samples into the textures, and
+    /// generate again the mipmaps of the textures which have them
+    ///
+    /// The default framebuffer is bound afterwards.
+    pub fn resolve(&mut self) {
+        let (width, height) = (self.size.0 as GLint, self.size.1 as GLint);
+        unsafe {
+            if let Some(msaa) = &self.msaa {
+                self.gl.BindFramebuffer(gl::READ_FRAMEBUFFER, msaa.fbo);
+                self.gl.BindFramebuffer(gl::DRAW_FRAMEBUFFER, self.fbo);
+                for idx in 0..self.colors.len() {
+                    let attachment = gl::COLOR_ATTACHMENT0 + idx as GLenum;
+                    self.gl.ReadBuffer(attachment);
+                    self.gl.DrawBuffers(1, &attachment);
+                    self.gl.BlitFramebuffer(0, 0, width, height, 0, 0, width, height,
+                        gl::COLOR_BUFFER_BIT, gl::NEAREST);
+                }
+                if let Some(depth) = &self.depth {
+                    let mask = if depth.format().has_stencil() {
+                        gl::DEPTH_BUFFER_BIT | gl::STENCIL_BUFFER_BIT
+                    } else { gl::DEPTH_BUFFER_BIT };
+                    self.gl.BlitFramebuffer(0, 0, width, height, 0, 0, width, height, mask, gl::NEAREST);
+                }
+                self.set_draw_buffers();
+            }
+            self.gl.BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+        for texture in self.colors.iter_mut().filter(|texture| texture.has_mipmaps()) {
+            texture.generate_mipmaps();
+        }
+    }
+
+    /// copy a resolved color attachment to the default framebuffer, scaled
+    /// to a size
+    pub fn blit_to_default(&self, attachment: usize, size: (u32, u32))
+    -> Result<(), CandlGraphicsError> {
+        if attachment >= self.colors.len() {
+            return Err(CandlGraphicsError::FramebufferError(format!("no color attachment {}", attachment)));
+        }
+        self.blit(attachment, size);
+        Ok(())
+    }
+
+    /// copy a color attachment, which must exist, to the default framebuffer
+    pub(super) fn blit(&self, attachment: usize, size: (u32, u32)) {
+        // the same size needs no filtering
+        let filter = if size == self.size { gl::NEAREST } else { gl::LINEAR };
+        unsafe {
+            self.gl.BindFramebuffer(gl::READ_FRAMEBUFFER, self.fbo);
+            self.gl.BindFramebuffer(gl::DRAW_FRAMEBUFFER, 0);
+            self.gl.ReadBuffer(gl::COLOR_ATTACHMENT0 + attachment as GLenum);
+            self.gl.BlitFramebuffer(0, 0, self.size.0 as GLint, self.size.1 as GLint,
+                0, 0, size.0 as GLint, size.1 as GLint, gl::COLOR_BUFFER_BIT, filter);
+            self.gl.BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+    }
+
+    /// resize the target, its attachments are created again, empty
+    pub fn resize(&mut self, size: (u32, u32)) -> Result<(), CandlGraphicsError> {
+        let colors: Vec<CandlTextureFormat> = self.colors.iter().map(|texture| texture.format()).collect();
+        let depth = self.depth.as_ref().map(|texture| texture.format());
+        let target = CandlRenderTarget::new(&self.gl, size, &colors, depth, self.samples)?;
+        std::mem::replace(self, target).delete();
+        Ok(())
+    }
+
+    /// get the GL pointer to the framebuffer holding the textures
+    pub fn get_ptr(&self) -> GLuint { self.fbo }
+
+    /// get the size of the target
+    pub fn size(&self) -> (u32, u32) { self.size }
+
+    /// get the number of samples, 0 without multisampling
+    pub fn samples(&self) -> u32 { self.samples }
+
+    /// get the texture of a color attachment
+    pub fn color_texture(&self, attachment: usize) -> Option<&CandlTexture> {
+        self.colors.get(attachment)
+    }
+
+    /// get a mutable reference to the texture of a color attachment, to
+    /// change its sampler or generate its mipmaps
+    pub fn color_texture_mut(&mut self, attachment: usize) -> Option<&mut CandlTexture> {
+        self.colors.get_mut(attachment)
+    }
+
+    /// get the texture of the depth attachment
+    pub fn depth_texture(&self) -> Option<&CandlTexture> { self.depth.as_ref() }
+
+    /// delete the target, with its textures
+    pub fn delete(self) {
+        unsafe {
+            if let Some(msaa) = &self.msaa {
+                self.gl.DeleteRenderbuffers(msaa.renderbuffers.len() as GLsizei, msaa.renderbuffers.as_ptr());
+                self.gl.DeleteFramebuffers(1, &msaa.fbo);
+            }
+            if self.fbo != 0 { self.gl.DeleteFramebuffers(1, &self.fbo); }
+        }
+        for texture in self.colors { texture.delete(); }
+        if let Some(texture) = self.depth { texture.delete(); }
+    }
+}
+
+/// attachment point of a depth format
+fn depth_attachment(format: CandlTextureFormat) -> GLenum {
+    if format.has_stencil() { gl::DEPTH_STENCIL_ATTACHMENT } else { gl::DEPTH_ATTACHMENT }
+}
+
+/// check the status of the bound framebuffer
+unsafe fn check_status(gl: &CandlGl) -> Result<(), CandlGraphicsError> {
+    let status = gl.CheckFramebufferStatus(gl::FRAMEBUFFER);
+    let name = match status {
+        gl::FRAMEBUFFER_COMPLETE => return Ok(()),
+        gl::FRAMEBUFFER_INCOMPLETE_ATTACHMENT => "incomplete attachment",
+        gl::FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT => "missing attachment",
+        gl::FRAMEBUFFER_INCOMPLETE_MULTISAMPLE => "incomplete multisample",
+        gl::FRAMEBUFFER_UNSUPPORTED => "unsupported formats",
+        _ => "incomplete framebuffer"
+    };
+    gl.BindFramebuffer(gl::FRAMEBUFFER, 0);
+    Err(CandlGraphicsError::FramebufferError(format!("{} (0x{:04X})", name, status)))
+}
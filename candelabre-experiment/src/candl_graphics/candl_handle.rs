@@ -0,0 +1,184 @@
+//! Generational handles
+//!
+//! The resources of `CandlGraphics` are stored in slot maps, and identified by
+//! handles made of a slot index and a generation. Removing a resource frees
+//! its slot and bumps its generation, so the old handles are detected as stale
+//! instead of pointing to the next resource stored in the slot.
+
+use super::CandlGraphicsError;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+
+/// handle to a resource stored in `CandlGraphics`
+///
+/// The handle is typed after the resource, it's cheap to copy, and stays
+/// valid until the resource is removed. It must only be used with the
+/// `CandlGraphics` it comes from, the handles of another one aren't detected.
+pub struct CandlHandle<T> {
+    index: u32,
+    generation: u32,
+    _kind: PhantomData<fn() -> T>
+}
+
+// implemented by hand, a derive would require the same traits on T
+
+impl<T> Clone for CandlHandle<T> {
+    fn clone(&self) -> Self { *self }
+}
+
+impl<T> Copy for CandlHandle<T> {}
+
+impl<T> PartialEq for CandlHandle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.index == other.index && self.generation == other.generation
+    }
+}
+
+impl<T> Eq for CandlHandle<T> {}
+
+impl<T> Hash for CandlHandle<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.index.hash(state);
+        self.generation.hash(state);
+    }
+}
+
+impl<T> fmt::Debug for CandlHandle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "CandlHandle({}v{})", self.index, self.generation)
+    }
+}
+
+/// a slot of a slot map, with the generation of its current (or next) value
+#[derive(Debug)]
+struct CandlSlot<T> {
+    generation: u32,
+    value: Option<T>
+}
+
+/// storage of a kind of resource, identified by handles
+#[derive(Debug)]
+pub(super) struct CandlSlotMap<T> {
+    kind: &'static str,
+    slots: Vec<CandlSlot<T>>,
+    free: Vec<u32>
+}
+
+impl<T> CandlSlotMap<T> {
+    /// create an empty slot map, with the name of the kind of resource for
+    /// the errors
+    pub(super) fn new(kind: &'static str) -> Self {
+        CandlSlotMap { kind, slots: vec!(), free: vec!() }
+    }
+
+    pub(super) fn insert(&mut self, value: T) -> CandlHandle<T> {
+        let index = match self.free.pop() {
+            Some(index) => {
+                self.slots[index as usize].value = Some(value);
+                index
+            }
+            None => {
+                self.slots.push(CandlSlot { generation: 0, value: Some(value) });
+                self.slots.len() as u32 - 1
+            }
+        };
+        CandlHandle { index, generation: self.slots[index as usize].generation, _kind: PhantomData }
+    }
+
+    fn stale(&self) -> CandlGraphicsError { CandlGraphicsError::StaleHandle(self.kind) }
+
+    pub(super) fn contains(&self, handle: CandlHandle<T>) -> bool {
+        match self.slots.get(handle.index as usize) {
+            Some(slot) => slot.generation == handle.generation && slot.value.is_some(),
+            None => false
+        }
+    }
+
+    pub(super) fn get(&self, handle: CandlHandle<T>) -> Result<&T, CandlGraphicsError> {
+        match self.slots.get(handle.index as usize) {
+            Some(CandlSlot { generation, value: Some(value) }) if *generation == handle.generation =>
+                Ok(value),
+            _ => Err(self.stale())
+        }
+    }
+
+    pub(super) fn get_mut(&mut self, handle: CandlHandle<T>) -> Result<&mut T, CandlGraphicsError> {
+        let stale = self.stale();
+        match self.slots.get_mut(handle.index as usize) {
+            Some(CandlSlot { generation, value: Some(value) }) if *generation == handle.generation =>
+                Ok(value),
+            _ => Err(stale)
+        }
+    }
+
+    pub(super) fn remove(&mut self, handle: CandlHandle<T>) -> Result<T, CandlGraphicsError> {
+        if !self.contains(handle) { return Err(self.stale()); }
+        let slot = &mut self.slots[handle.index as usize];
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(handle.index);
+        Ok(slot.value.take().unwrap())
+    }
+
+    /// remove all the values, all their handles becoming stale
+    pub(super) fn clear(&mut self) -> Vec<T> {
+        let mut values = vec!();
+        for (index, slot) in self.slots.iter_mut().enumerate() {
+            if let Some(value) = slot.value.take() {
+                slot.generation = slot.generation.wrapping_add(1);
+                self.free.push(index as u32);
+                values.push(value);
+            }
+        }
+        values
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_stale<T: fmt::Debug>(res: Result<T, CandlGraphicsError>) -> bool {
+        matches!(res, Err(CandlGraphicsError::StaleHandle("value")))
+    }
+
+    #[test]
+    fn detect_the_stale_handles() {
+        let mut map = CandlSlotMap::new("value");
+        let handle = map.insert(1);
+        assert_eq!(map.get(handle).ok(), Some(&1));
+        assert_eq!(map.remove(handle).ok(), Some(1));
+        assert!(!map.contains(handle));
+        assert!(is_stale(map.get(handle)));
+        assert!(is_stale(map.get_mut(handle)));
+        assert!(is_stale(map.remove(handle)));
+    }
+
+    #[test]
+    fn reuse_the_slots_with_a_new_generation() {
+        let mut map = CandlSlotMap::new("value");
+        let first = map.insert(1);
+        let other = map.insert(2);
+        map.remove(first).unwrap();
+        let second = map.insert(3);
+        assert_eq!(second.index, first.index);
+        assert_eq!(second.generation, first.generation + 1);
+        assert_ne!(second, first);
+        assert!(is_stale(map.get(first)));
+        *map.get_mut(second).unwrap() += 1;
+        assert_eq!(map.get(second).ok(), Some(&4));
+        assert_eq!(map.get(other).ok(), Some(&2));
+    }
+
+    #[test]
+    fn clear_all_the_slots() {
+        let mut map = CandlSlotMap::new("value");
+        let handles = [map.insert(1), map.insert(2)];
+        map.remove(handles[0]).unwrap();
+        assert_eq!(map.clear(), vec![2]);
+        assert!(handles.iter().all(|handle| is_stale(map.get(*handle))));
+        let handle = map.insert(3);
+        assert!(handles.iter().all(|old| *old != handle));
+        assert_eq!(map.get(handle).ok(), Some(&3));
+    }
+}
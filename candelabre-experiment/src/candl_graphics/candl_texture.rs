@@ -0,0 +1,470 @@
+//! Textures
+//!
+//! 2D textures, texture arrays and cube maps, with their internal format and
+//! their sampling parameters. With the `image` feature, the textures can also
+//! be decoded from PNG or JPEG files.
+
+use super::CandlGraphicsError;
+use candelabre_windowing::candl_gl::{self as gl, CandlGl};
+use candelabre_windowing::candl_gl::types::{GLenum, GLint, GLsizei, GLuint};
+use std::mem::size_of_val;
+use std::os::raw::c_void;
+use std::ptr::null;
+
+/// internal format of a texture
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum CandlTextureFormat {
+    /// one 8 bits channel
+    R8,
+    /// two 8 bits channels
+    RG8,
+    /// three 8 bits channels
+    RGB8,
+    /// four 8 bits channels
+    RGBA8,
+    /// three 8 bits channels, in the sRGB color space
+    SRGB8,
+    /// four 8 bits channels, in the sRGB color space with a linear alpha
+    SRGB8Alpha8,
+    /// one 16 bits float channel, uploaded from `f32`
+    R16F,
+    /// four 16 bits float channels, uploaded from `f32`
+    RGBA16F,
+    /// one 32 bits float channel
+    R32F,
+    /// two 32 bits float channels
+    RG32F,
+    /// four 32 bits float channels
+    RGBA32F,
+    /// 24 bits depth, uploaded from `u32`
+    Depth24,
+    /// 32 bits float depth
+    Depth32F,
+    /// 24 bits depth and 8 bits stencil, packed in a `u32`
+    Depth24Stencil8
+}
+
+impl CandlTextureFormat {
+    /// get the internal format, the format and the type of the pixels, and
+    /// the size of a pixel in bytes
+    pub(super) fn get_glenums(&self) -> (GLenum, GLenum, GLenum, usize) {
+        match self {
+            Self::R8 => (gl::R8, gl::RED, gl::UNSIGNED_BYTE, 1),
+            Self::RG8 => (gl::RG8, gl::RG, gl::UNSIGNED_BYTE, 2),
+            Self::RGB8 => (gl::RGB8, gl::RGB, gl::UNSIGNED_BYTE, 3),
+            Self::RGBA8 => (gl::RGBA8, gl::RGBA, gl::UNSIGNED_BYTE, 4),
+            Self::SRGB8 => (gl::SRGB8, gl::RGB, gl::UNSIGNED_BYTE, 3),
+            Self::SRGB8Alpha8 => (gl::SRGB8_ALPHA8, gl::RGBA, gl::UNSIGNED_BYTE, 4),
+            Self::R16F => (gl::R16F, gl::RED, gl::FLOAT, 4),
+            Self::RGBA16F => (gl::RGBA16F, gl::RGBA, gl::FLOAT, 16),
+            Self::R32F => (gl::R32F, gl::RED, gl::FLOAT, 4),
+            Self::RG32F => (gl::RG32F, gl::RG, gl::FLOAT, 8),
+            Self::RGBA32F => (gl::RGBA32F, gl::RGBA, gl::FLOAT, 16),
+            Self::Depth24 => (gl::DEPTH_COMPONENT24, gl::DEPTH_COMPONENT, gl::UNSIGNED_INT, 4),
+            Self::Depth32F => (gl::DEPTH_COMPONENT32F, gl::DEPTH_COMPONENT, gl::FLOAT, 4),
+            Self::Depth24Stencil8 =>
+                (gl::DEPTH24_STENCIL8, gl::DEPTH_STENCIL, gl::UNSIGNED_INT_24_8, 4)
+        }
+    }
+
+    /// check if the format holds a depth, and maybe a stencil
+    pub fn is_depth(&self) -> bool {
+        matches!(self, Self::Depth24 | Self::Depth32F | Self::Depth24Stencil8)
+    }
+
+    /// check if the format holds a stencil
+    pub fn has_stencil(&self) -> bool { *self == Self::Depth24Stencil8 }
+}
+
+/// kind of texture
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum CandlTextureKind {
+    /// 2D texture
+    Texture2D,
+    /// array of 2D textures, with its number of layers
+    Texture2DArray(u32),
+    /// cube map, with its six faces in the order +X, -X, +Y, -Y, +Z, -Z
+    CubeMap
+}
+
+impl CandlTextureKind {
+    fn get_glenum(&self) -> GLenum {
+        match self {
+            Self::Texture2D => gl::TEXTURE_2D,
+            Self::Texture2DArray(_) => gl::TEXTURE_2D_ARRAY,
+            Self::CubeMap => gl::TEXTURE_CUBE_MAP
+        }
+    }
+
+    /// number of layers, or faces
+    fn layers(&self) -> u32 {
+        match self {
+            Self::Texture2D => 1,
+            Self::Texture2DArray(layers) => *layers,
+            Self::CubeMap => 6
+        }
+    }
+}
+
+/// texture filter
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum CandlFilter {
+    /// nearest texel
+    Nearest,
+    /// linear interpolation between the texels
+    Linear
+}
+
+/// texture wrapping, outside of [0, 1]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum CandlWrap {
+    /// repeat the texture
+    Repeat,
+    /// repeat the texture, mirrored every other time
+    MirroredRepeat,
+    /// repeat the texels of the edges
+    ClampToEdge,
+    /// use the border color
+    ClampToBorder
+}
+
+impl CandlWrap {
+    fn get_glenum(&self) -> GLenum {
+        match self {
+            Self::Repeat => gl::REPEAT,
+            Self::MirroredRepeat => gl::MIRRORED_REPEAT,
+            Self::ClampToEdge => gl::CLAMP_TO_EDGE,
+            Self::ClampToBorder => gl::CLAMP_TO_BORDER
+        }
+    }
+}
+
+/// sampling parameters of a texture
+///
+/// The mipmap filter is only used once the mipmaps of the texture are
+/// generated, so the default sampler works with all the textures.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CandlSampler {
+    min_filter: CandlFilter,
+    mag_filter: CandlFilter,
+    mipmap_filter: Option<CandlFilter>,
+    wrap: (CandlWrap, CandlWrap, CandlWrap),
+    border_color: [f32; 4]
+}
+
+impl Default for CandlSampler {
+    fn default() -> Self {
+        CandlSampler {
+            min_filter: CandlFilter::Linear,
+            mag_filter: CandlFilter::Linear,
+            mipmap_filter: Some(CandlFilter::Linear),
+            wrap: (CandlWrap::Repeat, CandlWrap::Repeat, CandlWrap::Repeat),
+            border_color: [0.0, 0.0, 0.0, 0.0]
+        }
+    }
+}
+
+impl CandlSampler {
+    /// create a sampler with linear filters, and repeating the texture
+    pub fn new() -> Self { CandlSampler::default() }
+
+    /// set the filters, when the texture is minified and magnified
+    pub fn set_filters(mut self, min_filter: CandlFilter, mag_filter: CandlFilter) -> Self {
+        self.min_filter = min_filter;
+        self.mag_filter = mag_filter;
+        self
+    }
+
+    /// set the filter between the mipmaps, or `None` to use only the base
+    /// level
+    pub fn set_mipmap_filter(mut self, mipmap_filter: Option<CandlFilter>) -> Self {
+        self.mipmap_filter = mipmap_filter;
+        self
+    }
+
+    /// set the wrapping for all the coordinates
+    pub fn set_wrap(mut self, wrap: CandlWrap) -> Self {
+        self.wrap = (wrap, wrap, wrap);
+        self
+    }
+
+    /// set the wrapping for each coordinate (s, t, and r for the cube maps)
+    pub fn set_wraps(mut self, s: CandlWrap, t: CandlWrap, r: CandlWrap) -> Self {
+        self.wrap = (s, t, r);
+        self
+    }
+
+    /// set the border color, used with `CandlWrap::ClampToBorder`
+    pub fn set_border_color(mut self, color: [f32; 4]) -> Self {
+        self.border_color = color;
+        self
+    }
+
+    /// get the minification and magnification filters, for a texture with or
+    /// without mipmaps
+    fn filters(&self, mipmaps: bool) -> (GLenum, GLenum) {
+        let min_filter = match (self.min_filter, self.mipmap_filter.filter(|_| mipmaps)) {
+            (CandlFilter::Nearest, None) => gl::NEAREST,
+            (CandlFilter::Linear, None) => gl::LINEAR,
+            (CandlFilter::Nearest, Some(CandlFilter::Nearest)) => gl::NEAREST_MIPMAP_NEAREST,
+            (CandlFilter::Nearest, Some(CandlFilter::Linear)) => gl::NEAREST_MIPMAP_LINEAR,
+            (CandlFilter::Linear, Some(CandlFilter::Nearest)) => gl::LINEAR_MIPMAP_NEAREST,
+            (CandlFilter::Linear, Some(CandlFilter::Linear)) => gl::LINEAR_MIPMAP_LINEAR
+        };
+        let mag_filter = match self.mag_filter {
+            CandlFilter::Nearest => gl::NEAREST,
+            CandlFilter::Linear => gl::LINEAR
+        };
+        (min_filter, mag_filter)
+    }
+
+    /// apply the sampler to the texture bound to a target
+    unsafe fn apply(&self, gl: &CandlGl, target: GLenum, mipmaps: bool) {
+        let (min_filter, mag_filter) = self.filters(mipmaps);
+        gl.TexParameteri(target, gl::TEXTURE_MIN_FILTER, min_filter as GLint);
+        gl.TexParameteri(target, gl::TEXTURE_MAG_FILTER, mag_filter as GLint);
+        gl.TexParameteri(target, gl::TEXTURE_WRAP_S, self.wrap.0.get_glenum() as GLint);
+        gl.TexParameteri(target, gl::TEXTURE_WRAP_T, self.wrap.1.get_glenum() as GLint);
+        gl.TexParameteri(target, gl::TEXTURE_WRAP_R, self.wrap.2.get_glenum() as GLint);
+        gl.TexParameterfv(target, gl::TEXTURE_BORDER_COLOR, self.border_color.as_ptr());
+    }
+}
+
+/// candelabre texture
+///
+/// The data of the textures are given row by row, from the bottom of the
+/// image, then layer by layer (or face by face) for the arrays and the cube
+/// maps. The context must be current to create, update, bind or delete it.
+#[derive(Debug)]
+pub struct CandlTexture {
+    gl: CandlGl,
+    ptr: GLuint,
+    kind: CandlTextureKind,
+    format: CandlTextureFormat,
+    size: (u32, u32),
+    sampler: CandlSampler,
+    mipmaps: bool
+}
+
+impl CandlTexture {
+    /// create a texture, with its data or uninitialized
+    pub fn new<T: Copy>(
+        gl: &CandlGl,
+        kind: CandlTextureKind,
+        format: CandlTextureFormat,
+        size: (u32, u32),
+        data: Option<&[T]>
+    ) -> Result<CandlTexture, CandlGraphicsError> {
+        if size.0 == 0 || size.1 == 0 || kind.layers() == 0 {
+            return Err(CandlGraphicsError::TextureError(String::from("a texture can't be empty")));
+        }
+        if kind == CandlTextureKind::CubeMap && size.0 != size.1 {
+            return Err(CandlGraphicsError::TextureError(String::from("the faces of a cube map must be square")));
+        }
+        let (internal, pixel_format, pixel_type, pixel_size) = format.get_glenums();
+        let face_len = size.0 as usize * size.1 as usize * pixel_size;
+        if let Some(data) = data {
+            check_len(size_of_val(data), face_len * kind.layers() as usize)?;
+        }
+        let data_ptr = |layer: usize| match data {
+            Some(data) => unsafe { (data.as_ptr() as *const u8).add(layer * face_len) as *const c_void },
+            None => null()
+        };
+        let target = kind.get_glenum();
+        let sampler = CandlSampler::default();
+        let mut ptr = 0;
+        unsafe {
+            gl.GenTextures(1, &mut ptr);
+            gl.BindTexture(target, ptr);
+            gl.PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            let (width, height) = (size.0 as GLsizei, size.1 as GLsizei);
+            match kind {
+                CandlTextureKind::Texture2D => gl.TexImage2D(target, 0, internal as GLint,
+                    width, height, 0, pixel_format, pixel_type, data_ptr(0)),
+                CandlTextureKind::Texture2DArray(layers) => gl.TexImage3D(target, 0, internal as GLint,
+                    width, height, layers as GLsizei, 0, pixel_format, pixel_type, data_ptr(0)),
+                CandlTextureKind::CubeMap => for face in 0..6 {
+                    gl.TexImage2D(gl::TEXTURE_CUBE_MAP_POSITIVE_X + face, 0, internal as GLint,
+                        width, height, 0, pixel_format, pixel_type, data_ptr(face as usize));
+                }
+            }
+            sampler.apply(gl, target, false);
+            gl.BindTexture(target, 0);
+        }
+        Ok(CandlTexture { gl: gl.clone(), ptr, kind, format, size, sampler, mipmaps: false })
+    }
+
+    /// decode an image file (PNG or JPEG) in a 2D texture
+    ///
+    /// The image is flipped, to have its first row at the bottom as OpenGL
+    /// expects. The colors are in the sRGB color space if `srgb` is set.
+    #[cfg(feature = "image")]
+    pub fn from_image<P: AsRef<std::path::Path>>(gl: &CandlGl, path: P, srgb: bool)
+    -> Result<CandlTexture, CandlGraphicsError> {
+        let img = image::open(path).map_err(|e| CandlGraphicsError::TextureError(format!("{}", e)))?;
+        CandlTexture::from_dynamic_image(gl, img, srgb)
+    }
+
+    /// decode an image (PNG or JPEG) from memory in a 2D texture, see
+    /// `from_image()`
+    #[cfg(feature = "image")]
+    pub fn from_image_bytes(gl: &CandlGl, bytes: &[u8], srgb: bool)
+    -> Result<CandlTexture, CandlGraphicsError> {
+        let img = image::load_from_memory(bytes)
+            .map_err(|e| CandlGraphicsError::TextureError(format!("{}", e)))?;
+        CandlTexture::from_dynamic_image(gl, img, srgb)
+    }
+
+    #[cfg(feature = "image")]
+    fn from_dynamic_image(gl: &CandlGl, img: image::DynamicImage, srgb: bool)
+    -> Result<CandlTexture, CandlGraphicsError> {
+        let img = img.flipv().into_rgba8();
+        let format = if srgb { CandlTextureFormat::SRGB8Alpha8 } else { CandlTextureFormat::RGBA8 };
+        let size = img.dimensions();
+        CandlTexture::new(gl, CandlTextureKind::Texture2D, format, size, Some(&img.into_raw()[..]))
+    }
+
+    /// replace a part of a layer (or a face) of the texture, from an offset
+    /// in texels
+    pub fn update<T: Copy>(&mut self, layer: u32, offset: (u32, u32), size: (u32, u32), data: &[T])
+    -> Result<(), CandlGraphicsError> {
+        if layer >= self.kind.layers()
+            || !fits(offset.0, size.0, self.size.0) || !fits(offset.1, size.1, self.size.1) {
+            return Err(CandlGraphicsError::TextureError(String::from("the update overflows the texture")));
+        }
+        let (_, pixel_format, pixel_type, pixel_size) = self.format.get_glenums();
+        check_len(size_of_val(data), size.0 as usize * size.1 as usize * pixel_size)?;
+        let target = self.kind.get_glenum();
+        let (x, y) = (offset.0 as GLint, offset.1 as GLint);
+        let (width, height) = (size.0 as GLsizei, size.1 as GLsizei);
+        let data = data.as_ptr() as *const c_void;
+        unsafe {
+            self.gl.BindTexture(target, self.ptr);
+            self.gl.PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            match self.kind {
+                CandlTextureKind::Texture2D => self.gl.TexSubImage2D(target, 0, x, y,
+                    width, height, pixel_format, pixel_type, data),
+                CandlTextureKind::Texture2DArray(_) => self.gl.TexSubImage3D(target, 0, x, y,
+                    layer as GLint, width, height, 1, pixel_format, pixel_type, data),
+                CandlTextureKind::CubeMap => self.gl.TexSubImage2D(gl::TEXTURE_CUBE_MAP_POSITIVE_X + layer,
+                    0, x, y, width, height, pixel_format, pixel_type, data)
+            }
+            if self.mipmaps { self.gl.GenerateMipmap(target); }
+            self.gl.BindTexture(target, 0);
+        }
+        Ok(())
+    }
+
+    /// generate the mipmaps of the texture, they're generated again after
+    /// each update
+    pub fn generate_mipmaps(&mut self) {
+        let target = self.kind.get_glenum();
+        self.mipmaps = true;
+        unsafe {
+            self.gl.BindTexture(target, self.ptr);
+            self.gl.GenerateMipmap(target);
+            self.sampler.apply(&self.gl, target, true);
+            self.gl.BindTexture(target, 0);
+        }
+    }
+
+    /// set the sampling parameters
+    pub fn set_sampler(&mut self, sampler: CandlSampler) {
+        let target = self.kind.get_glenum();
+        self.sampler = sampler;
+        unsafe {
+            self.gl.BindTexture(target, self.ptr);
+            sampler.apply(&self.gl, target, self.mipmaps);
+            self.gl.BindTexture(target, 0);
+        }
+    }
+
+    /// bind the texture to a texture unit, the one to give to the sampler
+    /// uniform of a program
+    pub fn bind(&self, unit: u32) {
+        unsafe {
+            self.gl.ActiveTexture(gl::TEXTURE0 + unit);
+            self.gl.BindTexture(self.kind.get_glenum(), self.ptr);
+        }
+    }
+
+    /// get the GL pointer to the texture
+    pub fn get_ptr(&self) -> GLuint { self.ptr }
+
+    /// get the kind of the texture
+    pub fn kind(&self) -> CandlTextureKind { self.kind }
+
+    /// get the internal format of the texture
+    pub fn format(&self) -> CandlTextureFormat { self.format }
+
+    /// get the size of the texture (or of a layer, or a face), in texels
+    pub fn size(&self) -> (u32, u32) { self.size }
+
+    /// get the sampling parameters
+    pub fn sampler(&self) -> &CandlSampler { &self.sampler }
+
+    /// check if the mipmaps are generated
+    pub fn has_mipmaps(&self) -> bool { self.mipmaps }
+
+    /// delete the texture
+    pub fn delete(self) {
+        unsafe { self.gl.DeleteTextures(1, &self.ptr); }
+    }
+}
+
+/// check if a range of texels, from an offset, fits in a dimension
+fn fits(offset: u32, size: u32, max: u32) -> bool {
+    matches!(offset.checked_add(size), Some(end) if end <= max)
+}
+
+/// check the size of the data given for a texture
+fn check_len(len: usize, expected: usize) -> Result<(), CandlGraphicsError> {
+    if len == expected { Ok(()) } else {
+        Err(CandlGraphicsError::TextureError(
+            format!("{} bytes of data given, {} bytes expected", len, expected)
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_the_data_length() {
+        assert!(check_len(64, 64).is_ok());
+        match check_len(48, 64) {
+            Err(CandlGraphicsError::TextureError(message)) =>
+                assert_eq!(message, "48 bytes of data given, 64 bytes expected"),
+            res => panic!("expected a texture error, got {:?}", res)
+        }
+    }
+
+    #[test]
+    fn check_the_updated_ranges() {
+        assert!(fits(0, 16, 16));
+        assert!(fits(8, 4, 16));
+        assert!(!fits(8, 9, 16));
+        assert!(!fits(u32::MAX, 2, 16));
+    }
+
+    #[test]
+    fn map_the_filters_with_and_without_mipmaps() {
+        let sampler = CandlSampler::new();
+        assert_eq!(sampler.filters(false), (gl::LINEAR, gl::LINEAR));
+        assert_eq!(sampler.filters(true), (gl::LINEAR_MIPMAP_LINEAR, gl::LINEAR));
+        let sampler = sampler
+            .set_filters(CandlFilter::Nearest, CandlFilter::Linear)
+            .set_mipmap_filter(Some(CandlFilter::Nearest));
+        assert_eq!(sampler.filters(false), (gl::NEAREST, gl::LINEAR));
+        assert_eq!(sampler.filters(true), (gl::NEAREST_MIPMAP_NEAREST, gl::LINEAR));
+        let sampler = sampler.set_mipmap_filter(Some(CandlFilter::Linear));
+        assert_eq!(sampler.filters(true), (gl::NEAREST_MIPMAP_LINEAR, gl::LINEAR));
+        let sampler = sampler
+            .set_filters(CandlFilter::Linear, CandlFilter::Nearest)
+            .set_mipmap_filter(Some(CandlFilter::Nearest));
+        assert_eq!(sampler.filters(true), (gl::LINEAR_MIPMAP_NEAREST, gl::NEAREST));
+        let sampler = sampler.set_mipmap_filter(None);
+        assert_eq!(sampler.filters(true), (gl::LINEAR, gl::NEAREST));
+    }
+}
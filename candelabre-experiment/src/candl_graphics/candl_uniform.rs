@@ -0,0 +1,385 @@
+//! Program reflection and typed uniforms
+//!
+//! After linking, a program lists its active uniforms and attributes, with
+//! their types and locations. The uniforms are then set by name, with values
+//! checked against the reflected type.
+
+use super::CandlGraphicsError;
+use candelabre_windowing::candl_gl::{self as gl, CandlGl};
+use candelabre_windowing::candl_gl::types::{GLchar, GLenum, GLint, GLsizei, GLuint};
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::fmt;
+
+/// GLSL type of a uniform or an attribute
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum CandlGlslType {
+    /// `float`
+    Float,
+    /// `vec2`
+    Vec2,
+    /// `vec3`
+    Vec3,
+    /// `vec4`
+    Vec4,
+    /// `int`
+    Int,
+    /// `ivec2`
+    IVec2,
+    /// `ivec3`
+    IVec3,
+    /// `ivec4`
+    IVec4,
+    /// `uint`
+    UInt,
+    /// `uvec2`
+    UVec2,
+    /// `uvec3`
+    UVec3,
+    /// `uvec4`
+    UVec4,
+    /// `bool`
+    Bool,
+    /// `bvec2`
+    BVec2,
+    /// `bvec3`
+    BVec3,
+    /// `bvec4`
+    BVec4,
+    /// `mat2`
+    Mat2,
+    /// `mat3`
+    Mat3,
+    /// `mat4`
+    Mat4,
+    /// any sampler, with its OpenGL type (`GL_SAMPLER_2D`, etc), set with
+    /// the index of a texture unit
+    Sampler(GLenum),
+    /// any image, with its OpenGL type (`GL_IMAGE_2D`, etc), set with the
+    /// index of an image unit
+    Image(GLenum),
+    /// any other type, with its OpenGL type, which can't be set by candelabre
+    Other(GLenum)
+}
+
+impl CandlGlslType {
+    fn from_glenum(kind: GLenum) -> Self {
+        match kind {
+            gl::FLOAT => Self::Float,
+            gl::FLOAT_VEC2 => Self::Vec2,
+            gl::FLOAT_VEC3 => Self::Vec3,
+            gl::FLOAT_VEC4 => Self::Vec4,
+            gl::INT => Self::Int,
+            gl::INT_VEC2 => Self::IVec2,
+            gl::INT_VEC3 => Self::IVec3,
+            gl::INT_VEC4 => Self::IVec4,
+            gl::UNSIGNED_INT => Self::UInt,
+            gl::UNSIGNED_INT_VEC2 => Self::UVec2,
+            gl::UNSIGNED_INT_VEC3 => Self::UVec3,
+            gl::UNSIGNED_INT_VEC4 => Self::UVec4,
+            gl::BOOL => Self::Bool,
+            gl::BOOL_VEC2 => Self::BVec2,
+            gl::BOOL_VEC3 => Self::BVec3,
+            gl::BOOL_VEC4 => Self::BVec4,
+            gl::FLOAT_MAT2 => Self::Mat2,
+            gl::FLOAT_MAT3 => Self::Mat3,
+            gl::FLOAT_MAT4 => Self::Mat4,
+            gl::SAMPLER_1D | gl::SAMPLER_2D | gl::SAMPLER_3D | gl::SAMPLER_CUBE
+            | gl::SAMPLER_1D_SHADOW | gl::SAMPLER_2D_SHADOW | gl::SAMPLER_CUBE_SHADOW
+            | gl::SAMPLER_1D_ARRAY | gl::SAMPLER_2D_ARRAY
+            | gl::SAMPLER_1D_ARRAY_SHADOW | gl::SAMPLER_2D_ARRAY_SHADOW
+            | gl::SAMPLER_2D_MULTISAMPLE | gl::SAMPLER_2D_MULTISAMPLE_ARRAY
+            | gl::SAMPLER_2D_RECT | gl::SAMPLER_2D_RECT_SHADOW | gl::SAMPLER_BUFFER
+            | gl::SAMPLER_CUBE_MAP_ARRAY | gl::SAMPLER_CUBE_MAP_ARRAY_SHADOW
+            | gl::INT_SAMPLER_1D | gl::INT_SAMPLER_2D | gl::INT_SAMPLER_3D
+            | gl::INT_SAMPLER_CUBE | gl::INT_SAMPLER_1D_ARRAY | gl::INT_SAMPLER_2D_ARRAY
+            | gl::INT_SAMPLER_2D_MULTISAMPLE | gl::INT_SAMPLER_BUFFER
+            | gl::UNSIGNED_INT_SAMPLER_1D | gl::UNSIGNED_INT_SAMPLER_2D
+            | gl::UNSIGNED_INT_SAMPLER_3D | gl::UNSIGNED_INT_SAMPLER_CUBE
+            | gl::UNSIGNED_INT_SAMPLER_1D_ARRAY | gl::UNSIGNED_INT_SAMPLER_2D_ARRAY
+            | gl::UNSIGNED_INT_SAMPLER_2D_MULTISAMPLE | gl::UNSIGNED_INT_SAMPLER_BUFFER =>
+                Self::Sampler(kind),
+            gl::IMAGE_1D | gl::IMAGE_2D | gl::IMAGE_3D | gl::IMAGE_CUBE
+            | gl::IMAGE_1D_ARRAY | gl::IMAGE_2D_ARRAY | gl::IMAGE_BUFFER
+            | gl::INT_IMAGE_2D | gl::INT_IMAGE_3D | gl::INT_IMAGE_2D_ARRAY
+            | gl::UNSIGNED_INT_IMAGE_2D | gl::UNSIGNED_INT_IMAGE_3D
+            | gl::UNSIGNED_INT_IMAGE_2D_ARRAY => Self::Image(kind),
+            _ => Self::Other(kind)
+        }
+    }
+}
+
+impl fmt::Display for CandlGlslType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            Self::Float => write!(f, "float"),
+            Self::Vec2 => write!(f, "vec2"),
+            Self::Vec3 => write!(f, "vec3"),
+            Self::Vec4 => write!(f, "vec4"),
+            Self::Int => write!(f, "int"),
+            Self::IVec2 => write!(f, "ivec2"),
+            Self::IVec3 => write!(f, "ivec3"),
+            Self::IVec4 => write!(f, "ivec4"),
+            Self::UInt => write!(f, "uint"),
+            Self::UVec2 => write!(f, "uvec2"),
+            Self::UVec3 => write!(f, "uvec3"),
+            Self::UVec4 => write!(f, "uvec4"),
+            Self::Bool => write!(f, "bool"),
+            Self::BVec2 => write!(f, "bvec2"),
+            Self::BVec3 => write!(f, "bvec3"),
+            Self::BVec4 => write!(f, "bvec4"),
+            Self::Mat2 => write!(f, "mat2"),
+            Self::Mat3 => write!(f, "mat3"),
+            Self::Mat4 => write!(f, "mat4"),
+            Self::Sampler(kind) => write!(f, "sampler (0x{:04X})", kind),
+            Self::Image(kind) => write!(f, "image (0x{:04X})", kind),
+            Self::Other(kind) => write!(f, "unsupported type (0x{:04X})", kind)
+        }
+    }
+}
+
+/// active uniform or attribute of a program
+///
+/// The size is the number of elements for an array, 1 otherwise. The name of
+/// an array is given without the `[0]` added by the driver.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CandlActiveVariable {
+    name: String,
+    kind: CandlGlslType,
+    size: usize,
+    location: GLint
+}
+
+impl CandlActiveVariable {
+    /// get the name of the variable
+    pub fn name(&self) -> &str { &self.name }
+
+    /// get the GLSL type of the variable
+    pub fn kind(&self) -> CandlGlslType { self.kind }
+
+    /// get the number of elements of the variable
+    pub fn size(&self) -> usize { self.size }
+
+    /// get the location of the variable
+    pub fn location(&self) -> GLint { self.location }
+}
+
+/// value which can be given to a uniform
+///
+/// Implemented for the scalars, the vectors (`[f32; 3]` for a `vec3`), the
+/// column-major matrices (`[[f32; 4]; 4]` for a `mat4`), and the slices of
+/// them for the arrays of uniforms. The samplers and the images are set with
+/// an `i32`.
+pub trait CandlUniform {
+    /// check if the value can be given to a uniform of this type
+    fn accepts(&self, kind: CandlGlslType) -> bool;
+
+    /// get the number of elements of the value
+    fn count(&self) -> usize { 1 }
+
+    /// upload the value to a location of a program, directly when the
+    /// program is given, or to the program in use otherwise
+    ///
+    /// # Safety
+    ///
+    /// The location must come from the program, which must be in use if it's
+    /// not given. A program can only be given if the context supports
+    /// `glProgramUniform*` (OpenGL 4.1 or `GL_ARB_separate_shader_objects`).
+    /// The functions must be the ones of the current context.
+    unsafe fn upload(&self, gl: &CandlGl, program: Option<GLuint>, location: GLint);
+}
+
+macro_rules! impl_uniform {
+    ($ty:ty, $($kind:pat)|+ => $upload:ident / $program_upload:ident, |$value:ident| $($arg:expr),+) => {
+        impl CandlUniform for $ty {
+            fn accepts(&self, kind: CandlGlslType) -> bool { matches!(kind, $($kind)|+) }
+
+            unsafe fn upload(&self, gl: &CandlGl, program: Option<GLuint>, location: GLint) {
+                let $value = self;
+                match program {
+                    Some(program) => gl.$program_upload(program, location, $($arg),+),
+                    None => gl.$upload(location, $($arg),+)
+                }
+            }
+        }
+    };
+}
+
+macro_rules! impl_uniform_slice {
+    ($ty:ty, $($kind:pat)|+ => $upload:ident / $program_upload:ident $(, $arg:expr)*) => {
+        impl CandlUniform for &[$ty] {
+            fn accepts(&self, kind: CandlGlslType) -> bool { matches!(kind, $($kind)|+) }
+
+            fn count(&self) -> usize { self.len() }
+
+            unsafe fn upload(&self, gl: &CandlGl, program: Option<GLuint>, location: GLint) {
+                let count = self.len() as GLsizei;
+                match program {
+                    Some(program) =>
+                        gl.$program_upload(program, location, count, $($arg,)* self.as_ptr() as *const _),
+                    None => gl.$upload(location, count, $($arg,)* self.as_ptr() as *const _)
+                }
+            }
+        }
+    };
+}
+
+impl_uniform!(f32, CandlGlslType::Float => Uniform1f / ProgramUniform1f, |v| *v);
+impl_uniform!([f32; 2], CandlGlslType::Vec2 => Uniform2f / ProgramUniform2f, |v| v[0], v[1]);
+impl_uniform!([f32; 3], CandlGlslType::Vec3 => Uniform3f / ProgramUniform3f, |v| v[0], v[1], v[2]);
+impl_uniform!([f32; 4], CandlGlslType::Vec4 =>
+    Uniform4f / ProgramUniform4f, |v| v[0], v[1], v[2], v[3]);
+impl_uniform!(i32, CandlGlslType::Int | CandlGlslType::Bool
+    | CandlGlslType::Sampler(_) | CandlGlslType::Image(_) => Uniform1i / ProgramUniform1i, |v| *v);
+impl_uniform!([i32; 2], CandlGlslType::IVec2 | CandlGlslType::BVec2 =>
+    Uniform2i / ProgramUniform2i, |v| v[0], v[1]);
+impl_uniform!([i32; 3], CandlGlslType::IVec3 | CandlGlslType::BVec3 =>
+    Uniform3i / ProgramUniform3i, |v| v[0], v[1], v[2]);
+impl_uniform!([i32; 4], CandlGlslType::IVec4 | CandlGlslType::BVec4 =>
+    Uniform4i / ProgramUniform4i, |v| v[0], v[1], v[2], v[3]);
+impl_uniform!(u32, CandlGlslType::UInt => Uniform1ui / ProgramUniform1ui, |v| *v);
+impl_uniform!([u32; 2], CandlGlslType::UVec2 => Uniform2ui / ProgramUniform2ui, |v| v[0], v[1]);
+impl_uniform!([u32; 3], CandlGlslType::UVec3 => Uniform3ui / ProgramUniform3ui, |v| v[0], v[1], v[2]);
+impl_uniform!([u32; 4], CandlGlslType::UVec4 =>
+    Uniform4ui / ProgramUniform4ui, |v| v[0], v[1], v[2], v[3]);
+impl_uniform!(bool, CandlGlslType::Bool => Uniform1i / ProgramUniform1i, |v| *v as GLint);
+impl_uniform!([[f32; 2]; 2], CandlGlslType::Mat2 =>
+    UniformMatrix2fv / ProgramUniformMatrix2fv, |v| 1, gl::FALSE, v.as_ptr() as *const f32);
+impl_uniform!([[f32; 3]; 3], CandlGlslType::Mat3 =>
+    UniformMatrix3fv / ProgramUniformMatrix3fv, |v| 1, gl::FALSE, v.as_ptr() as *const f32);
+impl_uniform!([[f32; 4]; 4], CandlGlslType::Mat4 =>
+    UniformMatrix4fv / ProgramUniformMatrix4fv, |v| 1, gl::FALSE, v.as_ptr() as *const f32);
+
+impl_uniform_slice!(f32, CandlGlslType::Float => Uniform1fv / ProgramUniform1fv);
+impl_uniform_slice!([f32; 2], CandlGlslType::Vec2 => Uniform2fv / ProgramUniform2fv);
+impl_uniform_slice!([f32; 3], CandlGlslType::Vec3 => Uniform3fv / ProgramUniform3fv);
+impl_uniform_slice!([f32; 4], CandlGlslType::Vec4 => Uniform4fv / ProgramUniform4fv);
+impl_uniform_slice!(i32, CandlGlslType::Int | CandlGlslType::Bool
+    | CandlGlslType::Sampler(_) | CandlGlslType::Image(_) => Uniform1iv / ProgramUniform1iv);
+impl_uniform_slice!(u32, CandlGlslType::UInt => Uniform1uiv / ProgramUniform1uiv);
+impl_uniform_slice!([[f32; 4]; 4], CandlGlslType::Mat4 =>
+    UniformMatrix4fv / ProgramUniformMatrix4fv, gl::FALSE);
+
+/// the reflected variables of a program, with the cached locations
+#[derive(Debug, Default)]
+pub(super) struct CandlReflection {
+    uniforms: Vec<CandlActiveVariable>,
+    attributes: Vec<CandlActiveVariable>,
+    uniform_ids: HashMap<String, usize>,
+    element_locations: HashMap<String, GLint>
+}
+
+impl CandlReflection {
+    /// read the active uniforms and attributes of a linked program
+    pub(super) unsafe fn reflect(gl: &CandlGl, ptr: GLuint) -> Self {
+        let uniforms = active_variables(
+            gl,
+            ptr,
+            gl::ACTIVE_UNIFORMS,
+            gl::ACTIVE_UNIFORM_MAX_LENGTH,
+            |ptr, idx, max_len, len, size, kind, name| {
+                gl.GetActiveUniform(ptr, idx, max_len, len, size, kind, name)
+            },
+            |ptr, name| gl.GetUniformLocation(ptr, name)
+        );
+        let attributes = active_variables(
+            gl,
+            ptr,
+            gl::ACTIVE_ATTRIBUTES,
+            gl::ACTIVE_ATTRIBUTE_MAX_LENGTH,
+            |ptr, idx, max_len, len, size, kind, name| {
+                gl.GetActiveAttrib(ptr, idx, max_len, len, size, kind, name)
+            },
+            |ptr, name| gl.GetAttribLocation(ptr, name)
+        );
+        let uniform_ids = uniforms.iter().enumerate()
+            .map(|(idx, uniform)| (uniform.name.clone(), idx))
+            .collect();
+        CandlReflection { uniforms, attributes, uniform_ids, element_locations: HashMap::new() }
+    }
+
+    pub(super) fn uniforms(&self) -> &[CandlActiveVariable] { &self.uniforms }
+
+    pub(super) fn attributes(&self) -> &[CandlActiveVariable] { &self.attributes }
+
+    pub(super) fn uniform(&self, name: &str) -> Option<&CandlActiveVariable> {
+        self.uniform_ids.get(name).map(|idx| &self.uniforms[*idx])
+    }
+
+    pub(super) fn attribute(&self, name: &str) -> Option<&CandlActiveVariable> {
+        self.attributes.iter().find(|attribute| attribute.name == name)
+    }
+
+    /// find the location of a uniform, or of an element of an array
+    /// (`lights[2]`), with its type and the number of elements from it
+    pub(super) fn uniform_location(&mut self, gl: &CandlGl, ptr: GLuint, name: &str)
+    -> Result<(GLint, CandlGlslType, usize), CandlGraphicsError> {
+        if let Some(uniform) = self.uniform(name) {
+            return Ok((uniform.location, uniform.kind, uniform.size));
+        }
+        let unknown = || CandlGraphicsError::UniformError(format!("no active uniform \"{}\"", name));
+        let (base, idx) = match (name.rfind('['), name.ends_with(']')) {
+            (Some(open), true) => (&name[..open], name[open + 1..name.len() - 1].parse::<usize>()),
+            _ => return Err(unknown())
+        };
+        let (kind, size) = match (self.uniform(base), idx) {
+            (Some(uniform), Ok(idx)) if idx < uniform.size => (uniform.kind, uniform.size - idx),
+            _ => return Err(unknown())
+        };
+        let location = match self.element_locations.get(name) {
+            Some(location) => *location,
+            None => {
+                let c_name = CString::new(name).map_err(|_| unknown())?;
+                let location = unsafe { gl.GetUniformLocation(ptr, c_name.as_ptr()) };
+                self.element_locations.insert(name.to_string(), location);
+                location
+            }
+        };
+        Ok((location, kind, size))
+    }
+}
+
+/// list the active variables of a program, skipping the ones without location
+/// (built-in variables and members of uniform blocks)
+unsafe fn active_variables<G, L>(
+    gl: &CandlGl,
+    ptr: GLuint,
+    count_param: GLenum,
+    max_len_param: GLenum,
+    get_active: G,
+    get_location: L
+) -> Vec<CandlActiveVariable>
+where
+    G: Fn(GLuint, GLuint, GLsizei, *mut GLsizei, *mut GLint, *mut GLenum, *mut GLchar),
+    L: Fn(GLuint, *const GLchar) -> GLint
+{
+    let mut count: GLint = 0;
+    let mut max_len: GLint = 0;
+    gl.GetProgramiv(ptr, count_param, &mut count);
+    gl.GetProgramiv(ptr, max_len_param, &mut max_len);
+    let mut buf = vec![0u8; max_len.max(1) as usize];
+    let mut variables = Vec::with_capacity(count.max(0) as usize);
+    for idx in 0..count.max(0) as GLuint {
+        let mut len: GLsizei = 0;
+        let mut size: GLint = 0;
+        let mut kind: GLenum = 0;
+        get_active(ptr, idx, buf.len() as GLsizei, &mut len, &mut size, &mut kind,
+            buf.as_mut_ptr() as *mut GLchar);
+        let full_name = String::from_utf8_lossy(&buf[..len.max(0) as usize]).into_owned();
+        let c_name = match CString::new(full_name.clone()) {
+            Ok(c_name) => c_name,
+            Err(_) => continue
+        };
+        let location = get_location(ptr, c_name.as_ptr());
+        if location < 0 { continue; }
+        let mut name = full_name;
+        if name.ends_with("[0]") { name.truncate(name.len() - 3); }
+        variables.push(CandlActiveVariable {
+            name,
+            kind: CandlGlslType::from_glenum(kind),
+            size: size.max(1) as usize,
+            location
+        });
+    }
+    variables
+}
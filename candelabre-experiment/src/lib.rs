@@ -10,6 +10,8 @@
 
 #![deny(missing_docs)]
 
+pub use candelabre_derive::CandlVertex;
+
 // =======================================================================
 // =======================================================================
 //               CandlGraphics
@@ -18,73 +20,585 @@
 
 
 pub use self::candl_graphics::{
+    CandlActiveVariable,
+    CandlAttribFormat,
+    CandlAttribute,
+    CandlBuffer,
+    CandlBufferUsage,
+    CandlComponent,
+    CandlFilter,
+    CandlGlslType,
     CandlGraphics,
     CandlGraphicsDrawer,
     CandlGraphicsError,
+    CandlGlslProfile,
+    CandlHandle,
+    CandlIndex,
+    CandlPreprocessor,
+    CandlPrimitive,
     CandlProgram,
+    CandlProgramDesc,
+    CandlRenderTarget,
+    CandlSampler,
     CandlShader,
-    CandlShaderVariant
+    CandlShaderDiagnostic,
+    CandlShaderVariant,
+    CandlTexture,
+    CandlTextureFormat,
+    CandlTextureKind,
+    CandlUniform,
+    CandlVertex,
+    CandlVertexArray,
+    CandlVertexAttrib,
+    CandlVertexLayout,
+    CandlWrap
 };
 
 mod candl_graphics {
-    use candelabre_windowing::CandlRenderer;
+    mod candl_buffer;
+    mod candl_framebuffer;
+    mod candl_handle;
+    mod candl_texture;
+    mod candl_uniform;
+
+    pub use self::candl_buffer::{
+        CandlAttribFormat, CandlAttribute, CandlBuffer, CandlBufferUsage, CandlComponent,
+        CandlIndex, CandlPrimitive, CandlVertex, CandlVertexArray, CandlVertexAttrib,
+        CandlVertexLayout
+    };
+    pub use self::candl_framebuffer::CandlRenderTarget;
+    pub use self::candl_handle::CandlHandle;
+    pub use self::candl_texture::{
+        CandlFilter, CandlSampler, CandlTexture, CandlTextureFormat, CandlTextureKind, CandlWrap
+    };
+    pub use self::candl_uniform::{CandlActiveVariable, CandlGlslType, CandlUniform};
+    use self::candl_handle::CandlSlotMap;
+    use self::candl_uniform::CandlReflection;
+    use candelabre_windowing::{CandlContext, CandlError, CandlRenderer};
     use candelabre_windowing::CandlUpdate;
-    use gl::{self, types::GLuint};
+    use candelabre_windowing::candl_gl::{self as gl, CandlGl};
+    use candelabre_windowing::candl_gl::types::{GLchar, GLenum, GLint, GLsizei, GLuint};
+    use std::collections::HashMap;
+    use std::error::Error;
     use std::ffi::CString;
+    use std::fmt;
+    use std::fs;
+    use std::io;
     use std::marker::PhantomData;
-    use std::ptr::null;
-    
+    use std::path::{Path, PathBuf};
+    use std::ptr::{null, null_mut};
+    use std::time::{Duration, Instant, SystemTime};
+
     /// candelabre shader type
     /// 
     /// Because each shader is different, each shader needs its own variation.
-    #[derive(Debug, PartialEq)]
+    /// The tessellation shaders need OpenGL 4.0, and the compute shaders
+    /// OpenGL 4.3.
+    #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
     pub enum CandlShaderVariant {
         /// vertex shader
         VertexShader,
+        /// tessellation control shader
+        TessControlShader,
+        /// tessellation evaluation shader
+        TessEvaluationShader,
         /// geometry shader
         GeometryShader,
         /// fragment shader
-        FragmentShader
+        FragmentShader,
+        /// compute shader
+        ComputeShader
     }
 
     impl CandlShaderVariant {
         fn get_glenum(&self) -> gl::types::GLenum {
             match self {
                 Self::VertexShader => gl::VERTEX_SHADER,
+                Self::TessControlShader => gl::TESS_CONTROL_SHADER,
+                Self::TessEvaluationShader => gl::TESS_EVALUATION_SHADER,
                 Self::GeometryShader => gl::GEOMETRY_SHADER,
-                Self::FragmentShader => gl::FRAGMENT_SHADER
+                Self::FragmentShader => gl::FRAGMENT_SHADER,
+                Self::ComputeShader => gl::COMPUTE_SHADER
+            }
+        }
+
+        /// index of the stage in the pipeline
+        fn index(&self) -> usize {
+            match self {
+                Self::VertexShader => 0,
+                Self::TessControlShader => 1,
+                Self::TessEvaluationShader => 2,
+                Self::GeometryShader => 3,
+                Self::FragmentShader => 4,
+                Self::ComputeShader => 5
+            }
+        }
+
+        /// name of the define injected in the shaders of this variant
+        fn define_name(&self) -> &'static str {
+            match self {
+                Self::VertexShader => "CANDL_VERTEX_SHADER",
+                Self::TessControlShader => "CANDL_TESS_CONTROL_SHADER",
+                Self::TessEvaluationShader => "CANDL_TESS_EVALUATION_SHADER",
+                Self::GeometryShader => "CANDL_GEOMETRY_SHADER",
+                Self::FragmentShader => "CANDL_FRAGMENT_SHADER",
+                Self::ComputeShader => "CANDL_COMPUTE_SHADER"
+            }
+        }
+
+        /// lowest GLSL version supporting this variant, for a profile
+        fn min_version(&self, profile: CandlGlslProfile) -> u32 {
+            match (profile, self) {
+                (CandlGlslProfile::Es, Self::ComputeShader) => 310,
+                (CandlGlslProfile::Es, Self::VertexShader)
+                | (CandlGlslProfile::Es, Self::FragmentShader) => 300,
+                (CandlGlslProfile::Es, _) => 320,
+                (_, Self::TessControlShader) | (_, Self::TessEvaluationShader) => 400,
+                (_, Self::ComputeShader) => 430,
+                _ => 330
+            }
+        }
+
+        /// check if a context supports this variant, from its version or
+        /// its extensions
+        pub fn is_available(&self, gl: &CandlGl) -> bool {
+            match self {
+                Self::TessControlShader | Self::TessEvaluationShader =>
+                    gl.version() >= (4, 0) || gl.has_extension("GL_ARB_tessellation_shader"),
+                Self::ComputeShader =>
+                    gl.version() >= (4, 3) || gl.has_extension("GL_ARB_compute_shader"),
+                _ => true
+            }
+        }
+    }
+
+    impl fmt::Display for CandlShaderVariant {
+        fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+            match self {
+                Self::VertexShader => write!(f, "vertex shader"),
+                Self::TessControlShader => write!(f, "tessellation control shader"),
+                Self::TessEvaluationShader => write!(f, "tessellation evaluation shader"),
+                Self::GeometryShader => write!(f, "geometry shader"),
+                Self::FragmentShader => write!(f, "fragment shader"),
+                Self::ComputeShader => write!(f, "compute shader")
+            }
+        }
+    }
+
+    /// GLSL profile, written in the `#version` header
+    #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+    pub enum CandlGlslProfile {
+        /// desktop OpenGL, core profile
+        Core,
+        /// desktop OpenGL, compatibility profile
+        Compatibility,
+        /// OpenGL ES
+        Es
+    }
+
+    impl CandlGlslProfile {
+        /// check if `#line N` sets the number of the next line to N + 1, as
+        /// before GLSL 3.30 and GLSL ES 3.00, instead of N
+        fn legacy_line(&self, version: u32) -> bool {
+            match self {
+                Self::Es => version < 300,
+                _ => version < 330
             }
         }
     }
 
+    /// candelabre GLSL preprocessor
+    /// 
+    /// Build the source given to the driver from the source of a shader: the
+    /// `#version` header, the extensions, the injected `#define`s, and the
+    /// `#include "name"` directives, resolved with the files added to the
+    /// preprocessor. `#line` directives are inserted, so the diagnostics of the
+    /// driver point to the right line of the right file.
+    /// 
+    /// Each shader also gets a define naming its stage (`CANDL_VERTEX_SHADER`,
+    /// `CANDL_FRAGMENT_SHADER`, etc), to share a file between several stages.
+    /// If the source starts with its own `#version` directive, it replaces the
+    /// version and the profile of the preprocessor.
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct CandlPreprocessor {
+        version: Option<u32>,
+        profile: CandlGlslProfile,
+        extensions: Vec<(String, String)>,
+        defines: Vec<(String, String)>,
+        stage_defines: Vec<(CandlShaderVariant, String, String)>,
+        files: HashMap<String, String>
+    }
+
+    impl Default for CandlPreprocessor {
+        fn default() -> Self {
+            CandlPreprocessor {
+                version: None,
+                profile: CandlGlslProfile::Core,
+                extensions: vec![
+                    (String::from("GL_ARB_separate_shader_objects"), String::from("require"))
+                ],
+                defines: vec!(),
+                stage_defines: vec!(),
+                files: HashMap::new()
+            }
+        }
+    }
+
+    impl CandlPreprocessor {
+        /// create a preprocessor for the core profile, with the lowest version
+        /// supporting each stage, and requiring
+        /// `GL_ARB_separate_shader_objects`
+        pub fn new() -> Self { CandlPreprocessor::default() }
+
+        /// set the GLSL version, or `None` to use the lowest one supporting
+        /// the stage of each shader
+        pub fn set_version(mut self, version: Option<u32>) -> Self {
+            self.version = version;
+            self
+        }
+
+        /// set the GLSL profile
+        /// 
+        /// The extensions are kept, so remove the desktop ones with
+        /// `clear_extensions()` when switching to OpenGL ES.
+        pub fn set_profile(mut self, profile: CandlGlslProfile) -> Self {
+            self.profile = profile;
+            self
+        }
+
+        /// add an `#extension` directive, with its behavior (`require`,
+        /// `enable`, `warn` or `disable`)
+        pub fn add_extension<N, B>(mut self, name: N, behavior: B) -> Self
+        where N: Into<String>, B: Into<String> {
+            let name = name.into();
+            self.extensions.retain(|(ext, _)| *ext != name);
+            self.extensions.push((name, behavior.into()));
+            self
+        }
+
+        /// remove all the `#extension` directives
+        pub fn clear_extensions(mut self) -> Self {
+            self.extensions.clear();
+            self
+        }
+
+        /// inject a define in all the shaders, replacing the previous one with
+        /// the same name
+        pub fn define<N, V>(mut self, name: N, value: V) -> Self
+        where N: Into<String>, V: Into<String> {
+            let name = name.into();
+            self.defines.retain(|(def, _)| *def != name);
+            self.defines.push((name, value.into()));
+            self
+        }
+
+        /// inject a define only in the shaders of one stage
+        pub fn define_for<N, V>(mut self, variant: CandlShaderVariant, name: N, value: V) -> Self
+        where N: Into<String>, V: Into<String> {
+            let name = name.into();
+            self.stage_defines.retain(|(var, def, _)| *var != variant || *def != name);
+            self.stage_defines.push((variant, name, value.into()));
+            self
+        }
+
+        /// add a file which can be included by the shaders, replacing the
+        /// previous one with the same name
+        pub fn add_file<N, S>(mut self, name: N, src: S) -> Self
+        where N: Into<String>, S: Into<String> {
+            self.files.insert(name.into(), src.into());
+            self
+        }
+
+        /// get a copy of this preprocessor, with more defines, to build a
+        /// permutation of a program
+        pub fn permutation(&self, defines: &[(&str, &str)]) -> Self {
+            defines.iter().fold(self.clone(), |pre, (name, value)| pre.define(*name, *value))
+        }
+
+        /// process the source of a shader
+        /// 
+        /// It returns the source for the driver, and the names of the included
+        /// files, in the order of their source string numbers, starting at 1
+        /// (0 being the source itself).
+        pub fn process(&self, variant: CandlShaderVariant, src: &str)
+        -> Result<(String, Vec<String>), CandlGraphicsError> {
+            let lines: Vec<&str> = src.lines().collect();
+            let mut profile = self.profile;
+            let mut version = self.version.unwrap_or_else(|| variant.min_version(profile));
+            let mut first = 0;
+            if let Some(idx) = lines.iter().position(|line| !line.trim().is_empty()) {
+                let mut words = lines[idx].split_whitespace();
+                if words.next() == Some("#version") {
+                    version = words.next().and_then(|nb| nb.parse().ok()).ok_or_else(||
+                        CandlGraphicsError::PreprocessError(
+                            format!("line {}: bad version directive", idx + 1)
+                        )
+                    )?;
+                    profile = match words.next() {
+                        Some("es") => CandlGlslProfile::Es,
+                        Some("compatibility") => CandlGlslProfile::Compatibility,
+                        _ if version == 100 => CandlGlslProfile::Es,
+                        _ => CandlGlslProfile::Core
+                    };
+                    first = idx + 1;
+                }
+            }
+            let mut out = match (profile, version) {
+                (CandlGlslProfile::Es, 100) => String::from("#version 100\n"),
+                (CandlGlslProfile::Es, _) => format!("#version {} es\n", version),
+                (CandlGlslProfile::Compatibility, _) => format!("#version {} compatibility\n", version),
+                (CandlGlslProfile::Core, _) => format!("#version {} core\n", version)
+            };
+            for (name, behavior) in &self.extensions {
+                out.push_str(&format!("#extension {} : {}\n", name, behavior));
+            }
+            out.push_str(&format!("#define {}\n", variant.define_name()));
+            for (name, value) in &self.defines {
+                out.push_str(&format!("#define {} {}\n", name, value));
+            }
+            for (_, name, value) in self.stage_defines.iter().filter(|(var, _, _)| *var == variant) {
+                out.push_str(&format!("#define {} {}\n", name, value));
+            }
+            let mut expansion = CandlExpansion {
+                out,
+                legacy_line: profile.legacy_line(version),
+                sources: vec!(),
+                stack: vec!()
+            };
+            self.expand(&lines[first..], first as u32 + 1, 0, &mut expansion)?;
+            Ok((expansion.out, expansion.sources))
+        }
+
+        /// write some lines of a source, starting at the line `first`, and
+        /// expand their includes
+        fn expand(&self, lines: &[&str], first: u32, source: u32, expansion: &mut CandlExpansion)
+        -> Result<(), CandlGraphicsError> {
+            expansion.line_directive(first, source);
+            for (idx, line) in lines.iter().enumerate() {
+                let line_nb = first + idx as u32;
+                let name = match include_name(line) {
+                    None => {
+                        expansion.out.push_str(line);
+                        expansion.out.push('\n');
+                        continue;
+                    }
+                    Some(name) => name
+                };
+                let error = |message: String| {
+                    let file = match source {
+                        0 => "source",
+                        _ => &expansion.sources[source as usize - 1]
+                    };
+                    CandlGraphicsError::PreprocessError(format!("{}, line {}: {}", file, line_nb, message))
+                };
+                let name = name.ok_or_else(|| error(String::from("bad include directive")))?;
+                if expansion.stack.iter().any(|included| included == name) {
+                    return Err(error(format!("recursive include of \"{}\"", name)));
+                }
+                let src = self.files.get(name)
+                    .ok_or_else(|| error(format!("unknown include file \"{}\"", name)))?;
+                expansion.sources.push(name.to_string());
+                expansion.stack.push(name.to_string());
+                let included: Vec<&str> = src.lines().collect();
+                self.expand(&included, 1, expansion.sources.len() as u32, expansion)?;
+                expansion.stack.pop();
+                expansion.line_directive(line_nb + 1, source);
+            }
+            Ok(())
+        }
+    }
+
+    /// the state of the preprocessor while it expands the includes
+    struct CandlExpansion {
+        out: String,
+        legacy_line: bool,
+        sources: Vec<String>,
+        stack: Vec<String>
+    }
+
+    impl CandlExpansion {
+        /// write a directive to set the number of the next line
+        fn line_directive(&mut self, line: u32, source: u32) {
+            let line = if self.legacy_line { line - 1 } else { line };
+            self.out.push_str(&format!("#line {} {}\n", line, source));
+        }
+    }
+
+    /// get the name of the file included by a line, if it's an include
+    /// directive, `None` in the option if the directive is malformed
+    fn include_name(line: &str) -> Option<Option<&str>> {
+        let line = line.trim();
+        if !line.starts_with("#include") { return None; }
+        let name = line["#include".len()..].trim();
+        let quoted = (name.starts_with('"') && name.ends_with('"'))
+            || (name.starts_with('<') && name.ends_with('>'));
+        if quoted && name.len() > 2 { Some(Some(&name[1..name.len() - 1])) } else { Some(None) }
+    }
+
+    /// candelabre shader diagnostic
+    /// 
+    /// One message of the info log of a shader, with the line it's about, if
+    /// the driver gives it. The line is the one of the source given to
+    /// `CandlShader::new()`, or of one of the files it includes.
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct CandlShaderDiagnostic {
+        source: Option<String>,
+        line: Option<u32>,
+        message: String
+    }
+
+    impl CandlShaderDiagnostic {
+        /// parse an info log, with the names of the included files
+        ///
+        /// The drivers use different formats for the location of a message:
+        /// `0(12) : error` (NVIDIA), `0:12(5): error` (Mesa), or
+        /// `ERROR: 0:12: message` (AMD, Intel), they're all understood. A
+        /// location in an unknown source string is kept in the message.
+        fn parse_log(log: &str, sources: &[String]) -> Vec<Self> {
+            log.lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .map(|text| match CandlShaderDiagnostic::parse_location(text) {
+                    Some((source, line, message)) if source as usize <= sources.len() =>
+                        CandlShaderDiagnostic {
+                            source: (source as usize).checked_sub(1).map(|idx| sources[idx].clone()),
+                            line: Some(line).filter(|line| *line > 0),
+                            message
+                        },
+                    _ => CandlShaderDiagnostic {
+                        source: None,
+                        line: None,
+                        message: text.to_string()
+                    }
+                })
+                .collect()
+        }
+
+        /// find the source string number and the line number at the beginning
+        /// of a log line, and the message after them
+        fn parse_location(line: &str) -> Option<(u32, u32, String)> {
+            let (severity, rest) = match ["ERROR: ", "WARNING: "].iter()
+                .find(|prefix| line.starts_with(*prefix)) {
+                Some(prefix) => (Some(prefix[..prefix.len() - 2].to_lowercase()), &line[prefix.len()..]),
+                None => (None, line)
+            };
+            let source_end = rest.find(|c: char| !c.is_ascii_digit())?;
+            let source = rest[..source_end].parse().ok()?;
+            let rest = &rest[source_end..];
+            let (line_nb, rest) = match rest.chars().next()? {
+                '(' => {
+                    let end = rest.find(')')?;
+                    (rest[1..end].parse().ok()?, &rest[end + 1..])
+                }
+                ':' => {
+                    let rest = &rest[1..];
+                    let end = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+                    let line_nb = rest[..end].parse().ok()?;
+                    let rest = &rest[end..];
+                    // skip the column given by Mesa
+                    match (rest.starts_with('('), rest.find(')')) {
+                        (true, Some(col_end)) => (line_nb, &rest[col_end + 1..]),
+                        _ => (line_nb, rest)
+                    }
+                }
+                _ => return None
+            };
+            let message = rest.trim_start_matches(&[':', ' '][..]);
+            match severity {
+                Some(severity) => Some((source, line_nb, format!("{}: {}", severity, message))),
+                None => Some((source, line_nb, message.to_string()))
+            }
+        }
+
+        /// get the name of the included file the message is about, `None`
+        /// for the source of the shader itself
+        pub fn source(&self) -> Option<&str> { self.source.as_deref() }
+
+        /// get the line of the source, if any
+        pub fn line(&self) -> Option<u32> { self.line }
+
+        /// get the message from the driver
+        pub fn message(&self) -> &str { &self.message }
+    }
+
+    impl fmt::Display for CandlShaderDiagnostic {
+        fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+            match (&self.source, self.line) {
+                (Some(source), Some(line)) => write!(f, "{}, line {}: {}", source, line, self.message),
+                (None, Some(line)) => write!(f, "line {}: {}", line, self.message),
+                (Some(source), None) => write!(f, "{}: {}", source, self.message),
+                (None, None) => write!(f, "{}", self.message)
+            }
+        }
+    }
+
+    /// get the info log of a shader
+    unsafe fn shader_log(gl: &CandlGl, ptr: GLuint) -> String {
+        let mut len: GLint = 0;
+        gl.GetShaderiv(ptr, gl::INFO_LOG_LENGTH, &mut len);
+        let mut buf = vec![0u8; len.max(0) as usize];
+        gl.GetShaderInfoLog(ptr, len, null_mut(), buf.as_mut_ptr() as *mut GLchar);
+        String::from_utf8_lossy(&buf).trim_end_matches('\0').to_string()
+    }
+
+    /// get the info log of a program
+    unsafe fn program_log(gl: &CandlGl, ptr: GLuint) -> String {
+        let mut len: GLint = 0;
+        gl.GetProgramiv(ptr, gl::INFO_LOG_LENGTH, &mut len);
+        let mut buf = vec![0u8; len.max(0) as usize];
+        gl.GetProgramInfoLog(ptr, len, null_mut(), buf.as_mut_ptr() as *mut GLchar);
+        String::from_utf8_lossy(&buf).trim_end_matches('\0').to_string()
+    }
+
     /// candelabre shader
     /// 
     /// To be able to draw something, the renderer need some shaders. This is the
     /// purpose of this structure
     #[derive(Debug)]
     pub struct CandlShader {
+        gl: CandlGl,
         variant: CandlShaderVariant,
-        ptr: GLuint
+        ptr: GLuint,
+        diagnostics: Vec<CandlShaderDiagnostic>
     }
 
     impl CandlShader {
-        /// create a new shader
-        pub fn new(variant: CandlShaderVariant, src: &str) -> Result<Self, &'static str> {
+        /// create a new shader, with the default preprocessor
+        ///
+        /// If the compilation fails, the error holds the info log of the
+        /// driver, with the lines of `src`.
+        pub fn new(gl: &CandlGl, variant: CandlShaderVariant, src: &str)
+        -> Result<Self, CandlGraphicsError> {
+            CandlShader::new_with_preprocessor(gl, variant, src, &CandlPreprocessor::default())
+        }
+
+        /// create a new shader, processing its source with a preprocessor
+        pub fn new_with_preprocessor(
+            gl: &CandlGl,
+            variant: CandlShaderVariant,
+            src: &str,
+            preprocessor: &CandlPreprocessor
+        ) -> Result<Self, CandlGraphicsError> {
+            if !variant.is_available(gl) {
+                return Err(CandlGraphicsError::ShaderError("this shader variant isn't supported by the context"));
+            }
+            let (src, sources) = preprocessor.process(variant, src)?;
+            let c_src = CString::new(src)
+                .map_err(|_| CandlGraphicsError::ShaderError("the source contains a nul byte"))?;
             unsafe {
-                let ptr = gl::CreateShader(variant.get_glenum());
-                if ptr == 0 { return Err("bad pointer generated"); }
-                let mut pragma = String::from("#version 330 core\n#extension GL_ARB_separate_shader_objects : require\n");
-                pragma.push_str(src);
-                let c_src = CString::new(pragma.as_bytes()).unwrap();
-                gl::ShaderSource(ptr, 1, [c_src.as_ptr()].as_ptr(), null());
-                gl::CompileShader(ptr);
-                let mut compiled: gl::types::GLint = gl::FALSE.into();
-                gl::GetShaderiv(ptr, gl::COMPILE_STATUS, &mut compiled);
+                let ptr = gl.CreateShader(variant.get_glenum());
+                if ptr == 0 { return Err(CandlGraphicsError::ShaderError("bad pointer generated")); }
+                gl.ShaderSource(ptr, 1, [c_src.as_ptr()].as_ptr(), null());
+                gl.CompileShader(ptr);
+                let mut compiled: GLint = gl::FALSE.into();
+                gl.GetShaderiv(ptr, gl::COMPILE_STATUS, &mut compiled);
+                let log = shader_log(gl, ptr);
+                let diagnostics = CandlShaderDiagnostic::parse_log(&log, &sources);
                 if compiled == gl::TRUE.into() {
-                    Ok(CandlShader {variant, ptr})
+                    Ok(CandlShader {gl: gl.clone(), variant, ptr, diagnostics})
                 } else {
-                    gl::DeleteShader(ptr);
-                    Err("failed to compile the shader")
+                    gl.DeleteShader(ptr);
+                    Err(CandlGraphicsError::CompileError { stage: variant, log, diagnostics })
                 }
             }
         }
@@ -92,72 +606,300 @@ mod candl_graphics {
         /// get the GL pointer to the shader
         pub fn get_ptr(&self) -> GLuint { self.ptr.clone() }
 
+        /// get the messages of the driver after the compilation, the warnings
+        /// most of the time
+        pub fn get_diagnostics(&self) -> &[CandlShaderDiagnostic] { &self.diagnostics }
+
         /// check variant of the shader
         pub fn check_variant(&self, variant: CandlShaderVariant) -> bool {
             self.variant == variant
         }
+
+        /// delete the shader, the programs linked with it keep working
+        pub fn delete(self) {
+            unsafe { self.gl.DeleteShader(self.ptr); }
+        }
+    }
+
+    /// candelabre program description
+    /// 
+    /// The sources of the shaders of a program, to build it in one call with
+    /// `CandlProgram::from_desc()`.
+    #[derive(Clone, Debug, Default, PartialEq)]
+    pub struct CandlProgramDesc {
+        stages: Vec<(CandlShaderVariant, String)>
+    }
+
+    impl CandlProgramDesc {
+        /// create an empty description
+        pub fn new() -> Self { CandlProgramDesc::default() }
+
+        /// set the source of a stage, replacing the previous one
+        pub fn set_stage<S: Into<String>>(mut self, variant: CandlShaderVariant, src: S) -> Self {
+            let src = src.into();
+            match self.stages.iter_mut().find(|(v, _)| *v == variant) {
+                Some(stage) => stage.1 = src,
+                None => self.stages.push((variant, src))
+            }
+            self
+        }
+
+        /// set the source of the vertex shader
+        pub fn set_vertex<S: Into<String>>(self, src: S) -> Self {
+            self.set_stage(CandlShaderVariant::VertexShader, src)
+        }
+
+        /// set the source of the tessellation control shader
+        pub fn set_tess_control<S: Into<String>>(self, src: S) -> Self {
+            self.set_stage(CandlShaderVariant::TessControlShader, src)
+        }
+
+        /// set the source of the tessellation evaluation shader
+        pub fn set_tess_evaluation<S: Into<String>>(self, src: S) -> Self {
+            self.set_stage(CandlShaderVariant::TessEvaluationShader, src)
+        }
+
+        /// set the source of the geometry shader
+        pub fn set_geometry<S: Into<String>>(self, src: S) -> Self {
+            self.set_stage(CandlShaderVariant::GeometryShader, src)
+        }
+
+        /// set the source of the fragment shader
+        pub fn set_fragment<S: Into<String>>(self, src: S) -> Self {
+            self.set_stage(CandlShaderVariant::FragmentShader, src)
+        }
+
+        /// set the source of the compute shader
+        pub fn set_compute<S: Into<String>>(self, src: S) -> Self {
+            self.set_stage(CandlShaderVariant::ComputeShader, src)
+        }
+
+        /// set the source of a stage from a file, replacing the previous one
+        pub fn load_stage<P: AsRef<Path>>(self, variant: CandlShaderVariant, path: P)
+        -> Result<Self, CandlGraphicsError> {
+            let path = path.as_ref();
+            match fs::read_to_string(path) {
+                Ok(src) => Ok(self.set_stage(variant, src)),
+                Err(error) => Err(CandlGraphicsError::FileError { path: path.to_path_buf(), error })
+            }
+        }
+
+        /// get the stages of the program, with their sources
+        pub fn stages(&self) -> &[(CandlShaderVariant, String)] { &self.stages }
     }
 
     /// candelabre program
     /// 
     /// With some shaders you can build a program, which then will be used by
-    /// an OpenGL context to render your creation into your window. You can
-    /// attach a lot of different shaders to a program, but to make things
-    /// working, and because CandlGraphics isn't a fully featured renderer, the
-    /// CandlProgram only takes vertex, geometry and fragment shaders.
+    /// an OpenGL context to render your creation into your window. A program
+    /// takes either the graphics stages (vertex, tessellation, geometry and
+    /// fragment shaders), or only a compute shader. The `get_*s()` methods
+    /// give the pointers of the shaders it was linked with, unless it owns
+    /// them (built from a description).
     #[derive(Debug)]
     pub struct CandlProgram {
+        gl: CandlGl,
         ptr: GLuint,
+        vs: Option<GLuint>,
+        tcs: Option<GLuint>,
+        tes: Option<GLuint>,
+        gs: Option<GLuint>,
         fs: Option<GLuint>,
-        //gs: Option<GLuint>,
-        vs: Option<GLuint>
+        cs: Option<GLuint>,
+        compute: bool,
+        direct_uniforms: bool,
+        reflection: CandlReflection
     }
 
     impl CandlProgram {
         /// create a new program
-        pub fn new(fs: Option<&CandlShader>, /*gs: Option<&CandlShader>,*/ vs: Option<&CandlShader>)
-        -> Result<CandlProgram, &'static str> {
-            if fs.is_some() && fs.as_ref().unwrap().check_variant(CandlShaderVariant::FragmentShader) {
-                Err("the fragment shader (fs) is not of the right variant")
-            //} else if gs.is_some() && gs.as_ref().unwrap().check_variant(CandlShaderVariant::GeometryShader) {
-            //    Err("the geometry shader (gs) is not of the right variant")
-            } else if vs.is_some() && vs.as_ref().unwrap().check_variant(CandlShaderVariant::VertexShader) {
-                Err("the vertex shader (vs) is not of the right variant")
+        pub fn new(gl: &CandlGl, fs: Option<&CandlShader>, vs: Option<&CandlShader>)
+        -> Result<CandlProgram, CandlGraphicsError> {
+            if fs.is_some() && !fs.as_ref().unwrap().check_variant(CandlShaderVariant::FragmentShader) {
+                Err(CandlGraphicsError::ProgramError("the fragment shader (fs) is not of the right variant"))
+            } else if vs.is_some() && !vs.as_ref().unwrap().check_variant(CandlShaderVariant::VertexShader) {
+                Err(CandlGraphicsError::ProgramError("the vertex shader (vs) is not of the right variant"))
+            } else {
+                let shaders: Vec<&CandlShader> = vs.into_iter().chain(fs).collect();
+                CandlProgram::from_shaders(gl, &shaders)
+            }
+        }
+
+        /// create a new program with any stages
+        ///
+        /// Each stage can only be given once, a compute shader can't be linked
+        /// with other stages, and the tessellation control shader needs a
+        /// tessellation evaluation shader.
+        pub fn from_shaders(gl: &CandlGl, shaders: &[&CandlShader])
+        -> Result<CandlProgram, CandlGraphicsError> {
+            let mut stages: [Option<GLuint>; 6] = [None; 6];
+            for shader in shaders {
+                let stage = &mut stages[shader.variant.index()];
+                if stage.is_some() {
+                    return Err(CandlGraphicsError::ProgramError("a stage is given more than once"));
+                }
+                *stage = Some(shader.get_ptr());
+            }
+            let [vs, tcs, tes, gs, fs, cs] = stages;
+            if shaders.is_empty() {
+                Err(CandlGraphicsError::ProgramError("a program needs at least one shader"))
+            } else if cs.is_some() && shaders.len() > 1 {
+                Err(CandlGraphicsError::ProgramError("a compute shader can't be linked with other stages"))
+            } else if tcs.is_some() && tes.is_none() {
+                Err(CandlGraphicsError::ProgramError("the tessellation control shader needs a tessellation evaluation shader"))
             } else {
-                //
                 unsafe {
-                    let ptr = gl::CreateProgram();
-                    let fs = CandlProgram::attach_shader(ptr.clone(), fs);
-                    //let gs = CandlProgram::attach_shader(program.clone(), gs);
-                    let vs = CandlProgram::attach_shader(ptr.clone(), vs);
-                    gl::LinkProgram(ptr);
-                    //
-                    //gl::UseProgram(program);
-                    //
-                    Ok(Self {ptr, fs, /*gs,*/ vs})
+                    let ptr = gl.CreateProgram();
+                    for shader in shaders { gl.AttachShader(ptr, shader.get_ptr()); }
+                    gl.LinkProgram(ptr);
+                    let mut linked: GLint = gl::FALSE.into();
+                    gl.GetProgramiv(ptr, gl::LINK_STATUS, &mut linked);
+                    if linked != gl::TRUE.into() {
+                        let log = program_log(gl, ptr);
+                        gl.DeleteProgram(ptr);
+                        return Err(CandlGraphicsError::LinkError(log));
+                    }
+                    let reflection = CandlReflection::reflect(gl, ptr);
+                    let compute = cs.is_some();
+                    let direct_uniforms = gl.version() >= (4, 1)
+                        || gl.has_extension("GL_ARB_separate_shader_objects");
+                    Ok(Self {gl: gl.clone(), ptr, vs, tcs, tes, gs, fs, cs, compute, direct_uniforms, reflection})
                 }
             }
         }
 
+        /// create a new program from its description, compiling its shaders
+        /// with the default preprocessor
+        ///
+        /// The shaders are only owned by the program, and released with it, so
+        /// the program doesn't give their pointers.
+        pub fn from_desc(gl: &CandlGl, desc: &CandlProgramDesc)
+        -> Result<CandlProgram, CandlGraphicsError> {
+            CandlProgram::from_desc_with_preprocessor(gl, desc, &CandlPreprocessor::default())
+        }
+
+        /// create a new program from its description, processing the sources
+        /// of its shaders with a preprocessor
+        pub fn from_desc_with_preprocessor(
+            gl: &CandlGl,
+            desc: &CandlProgramDesc,
+            preprocessor: &CandlPreprocessor
+        ) -> Result<CandlProgram, CandlGraphicsError> {
+            let mut shaders = Vec::with_capacity(desc.stages().len());
+            let mut res = Ok(());
+            for (variant, src) in desc.stages() {
+                match CandlShader::new_with_preprocessor(gl, *variant, src, preprocessor) {
+                    Ok(shader) => shaders.push(shader),
+                    Err(err) => { res = Err(err); break; }
+                }
+            }
+            let res = res.and_then(|_| {
+                let refs: Vec<&CandlShader> = shaders.iter().collect();
+                CandlProgram::from_shaders(gl, &refs)
+            });
+            // flagged for deletion, released when the program is deleted
+            for shader in shaders { shader.delete(); }
+            res.map(|program| CandlProgram {
+                vs: None, tcs: None, tes: None, gs: None, fs: None, cs: None, ..program
+            })
+        }
+
         /// get the fragment shader pointer
         pub fn get_fs(&self) -> &Option<GLuint> { &self.fs }
 
         /// get the geometry shader pointer
-        //pub fn get_gs(&self) -> &Option<GLuint> { &self.gs }
+        pub fn get_gs(&self) -> &Option<GLuint> { &self.gs }
+
+        /// get the tessellation control shader pointer
+        pub fn get_tcs(&self) -> &Option<GLuint> { &self.tcs }
+
+        /// get the tessellation evaluation shader pointer
+        pub fn get_tes(&self) -> &Option<GLuint> { &self.tes }
 
         /// get the vertex shader pointer
         pub fn get_vs(&self) -> &Option<GLuint> { &self.vs }
 
+        /// get the compute shader pointer
+        pub fn get_cs(&self) -> &Option<GLuint> { &self.cs }
+
         /// get the pointer to a program
         pub fn get_ptr(&self) -> GLuint { self.ptr.clone() }
 
-        fn attach_shader(program: GLuint, sh: Option<&CandlShader>) -> Option<GLuint> {
-            if let Some(sh) = sh {
-                let ptr = sh.get_ptr();
-                unsafe { gl::AttachShader(program, ptr); }
-                Some(ptr)
+        /// delete the program
+        pub fn delete(self) {
+            unsafe { self.gl.DeleteProgram(self.ptr); }
+        }
+
+        /// get the active uniforms of the program
+        pub fn uniforms(&self) -> &[CandlActiveVariable] { self.reflection.uniforms() }
+
+        /// get the active attributes of the program
+        pub fn attributes(&self) -> &[CandlActiveVariable] { self.reflection.attributes() }
+
+        /// get an active uniform by its name
+        pub fn get_uniform(&self, name: &str) -> Option<&CandlActiveVariable> {
+            self.reflection.uniform(name)
+        }
+
+        /// get an active attribute by its name
+        pub fn get_attribute(&self, name: &str) -> Option<&CandlActiveVariable> {
+            self.reflection.attribute(name)
+        }
+
+        /// set the value of a uniform, after checking its type
+        ///
+        /// The name can also be an element of an array (`lights[2]`), its
+        /// location is then cached. The value is set directly in the program
+        /// when the context supports it (OpenGL 4.1 or
+        /// `GL_ARB_separate_shader_objects`), otherwise the program becomes
+        /// the one in use.
+        pub fn set_uniform<T: CandlUniform>(&mut self, name: &str, value: T)
+        -> Result<(), CandlGraphicsError> {
+            let (location, kind, size) = self.reflection.uniform_location(&self.gl, self.ptr, name)?;
+            if !value.accepts(kind) {
+                return Err(CandlGraphicsError::UniformError(
+                    format!("the uniform \"{}\" is a {}", name, kind)
+                ));
+            }
+            if value.count() > size {
+                return Err(CandlGraphicsError::UniformError(
+                    format!("the uniform \"{}\" has {} elements, not {}", name, size, value.count())
+                ));
+            }
+            unsafe {
+                if self.direct_uniforms {
+                    value.upload(&self.gl, Some(self.ptr), location);
+                } else {
+                    self.gl.UseProgram(self.ptr);
+                    value.upload(&self.gl, None, location);
+                }
+            }
+            Ok(())
+        }
+
+        /// run a compute program, with the number of work groups in each
+        /// dimension
+        pub fn dispatch(&self, groups: (u32, u32, u32)) -> Result<(), CandlGraphicsError> {
+            if !self.compute {
+                return Err(CandlGraphicsError::ProgramError("this program has no compute shader"));
+            }
+            unsafe {
+                self.gl.UseProgram(self.ptr);
+                self.gl.DispatchCompute(groups.0, groups.1, groups.2);
+            }
+            Ok(())
+        }
+
+        /// validate the program against the current OpenGL state, to check if
+        /// it can be used to draw
+        pub fn validate(&self) -> Result<(), CandlGraphicsError> {
+            unsafe {
+                self.gl.ValidateProgram(self.ptr);
+                let mut valid: GLint = gl::FALSE.into();
+                self.gl.GetProgramiv(self.ptr, gl::VALIDATE_STATUS, &mut valid);
+                if valid == gl::TRUE.into() { Ok(()) }
+                else { Err(CandlGraphicsError::ValidationError(program_log(&self.gl, self.ptr))) }
             }
-            else { None }
         }
     }
 
@@ -169,9 +911,74 @@ mod candl_graphics {
         /// error with the shader (creation, compilation, etc)
         ShaderError(&'static str),
         /// error with the program generation
-        ProgramError(&'static str)
+        ProgramError(&'static str),
+        /// the driver failed to compile a shader
+        CompileError {
+            /// the stage of the shader
+            stage: CandlShaderVariant,
+            /// the raw info log of the driver
+            log: String,
+            /// the messages of the info log, with the lines of the source
+            diagnostics: Vec<CandlShaderDiagnostic>
+        },
+        /// the driver failed to link a program, with its info log
+        LinkError(String),
+        /// the program can't be used with the current state, with the info
+        /// log of the driver
+        ValidationError(String),
+        /// the preprocessor failed to process the source of a shader
+        PreprocessError(String),
+        /// a uniform doesn't exist, or doesn't take the value
+        UniformError(String),
+        /// a buffer or a vertex array can't be used this way
+        BufferError(String),
+        /// a texture can't be created, decoded or updated
+        TextureError(String),
+        /// a render target is incomplete, or can't be used this way
+        FramebufferError(String),
+        /// a handle doesn't point to a resource anymore, with the kind of the
+        /// resource
+        StaleHandle(&'static str),
+        /// the renderer has no context yet, or anymore
+        NoContext,
+        /// a shader file can't be read
+        FileError {
+            /// the path of the file
+            path: PathBuf,
+            /// the error returned by the file system
+            error: io::Error
+        }
+    }
+
+    impl fmt::Display for CandlGraphicsError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+            match self {
+                Self::ShaderError(e) => write!(f, "Candelabre shader error: {}", e),
+                Self::ProgramError(e) => write!(f, "Candelabre program error: {}", e),
+                Self::CompileError { stage, diagnostics, .. } => {
+                    write!(f, "Failed to compile the {}:", stage)?;
+                    for diagnostic in diagnostics { write!(f, "\n  {}", diagnostic)?; }
+                    Ok(())
+                }
+                Self::LinkError(log) => write!(f, "Failed to link the program:\n{}", log),
+                Self::ValidationError(log) => write!(f, "Invalid program:\n{}", log),
+                Self::PreprocessError(e) => write!(f, "GLSL preprocessor error: {}", e),
+                Self::UniformError(e) => write!(f, "Candelabre uniform error: {}", e),
+                Self::BufferError(e) => write!(f, "Candelabre buffer error: {}", e),
+                Self::TextureError(e) => write!(f, "Candelabre texture error: {}", e),
+                Self::FramebufferError(e) => write!(f, "Candelabre framebuffer error: {}", e),
+                Self::StaleHandle(kind) => write!(f,
+                    "Candelabre handle error: the {} was removed",
+                    kind),
+                Self::NoContext => write!(f, "Candelabre graphics error: no OpenGL context set up"),
+                Self::FileError { path, error } =>
+                    write!(f, "Failed to read the shader file {}: {}", path.display(), error)
+            }
+        }
     }
 
+    impl Error for CandlGraphicsError {}
+
     /// Draw function trait
     /// 
     /// There is several way to handle a redraw function, by using a closure is
@@ -186,19 +993,76 @@ mod candl_graphics {
         fn execute(&self, state: Option<&S>, opts: Option<&O>);
     }
 
+    /// program loaded from files, with the modification time of each file
+    /// when the program was last built, to know when to reload it
+    #[derive(Debug)]
+    struct CandlWatchedProgram {
+        program: CandlHandle<CandlProgram>,
+        files: Vec<(CandlShaderVariant, PathBuf, Option<SystemTime>)>
+    }
+
+    impl CandlWatchedProgram {
+        fn changed(&self) -> bool {
+            self.files.iter().any(|(_, path, mtime)| match modified(path) {
+                Some(current) => Some(current) != *mtime,
+                None => false
+            })
+        }
+
+        /// read the files, with their modification time, to save once the
+        /// program is built, so a failed build is tried again
+        fn load(files: &[(CandlShaderVariant, PathBuf, Option<SystemTime>)])
+        -> Result<(CandlProgramDesc, Vec<Option<SystemTime>>), CandlGraphicsError> {
+            let mut desc = CandlProgramDesc::new();
+            let mut mtimes = Vec::with_capacity(files.len());
+            for (variant, path, _) in files {
+                mtimes.push(modified(path));
+                desc = desc.load_stage(*variant, path)?;
+            }
+            Ok((desc, mtimes))
+        }
+
+        /// save the modification times of the files of the built program
+        fn built(&mut self, mtimes: Vec<Option<SystemTime>>) {
+            for ((_, _, mtime), built) in self.files.iter_mut().zip(mtimes) { *mtime = built; }
+        }
+    }
+
+    /// last modification time of a file, if the file system can tell it
+    fn modified(path: &Path) -> Option<SystemTime> {
+        fs::metadata(path).and_then(|meta| meta.modified()).ok()
+    }
+
     /// Candelabre Graphics
     /// 
     /// Structure to handle all direct OpenGL operations. It's the foundation stone
     /// for candelabre-widget.
+    /// 
+    /// The resources are created with the functions of the context given to
+    /// `setup()`, so the `gen_*` methods fail with `NoContext` before. They're
+    /// all released by `teardown()`, and forgotten when the context is
+    /// recreated, their handles becoming stale in both cases.
     #[derive(Debug)]
     pub struct CandlGraphics<F, S, M, O>
     where F: CandlGraphicsDrawer<S, M, O>, S: CandlUpdate<M> {
+        gl: Option<CandlGl>,
         clear_color: [f32; 4],
         size: (u32, u32),
         scale_factor: f64,
         //
-        shaders: Vec<CandlShader>,
-        programs: Vec<CandlProgram>,
+        shaders: CandlSlotMap<CandlShader>,
+        programs: CandlSlotMap<CandlProgram>,
+        buffers: CandlSlotMap<CandlBuffer>,
+        vertex_arrays: CandlSlotMap<CandlVertexArray>,
+        textures: CandlSlotMap<CandlTexture>,
+        render_targets: CandlSlotMap<CandlRenderTarget>,
+        frame_target: Option<CandlHandle<CandlRenderTarget>>,
+        preprocessor: CandlPreprocessor,
+        //
+        watched: Vec<CandlWatchedProgram>,
+        reload_interval: Option<Duration>,
+        last_check: Instant,
+        reload_errors: Vec<(CandlHandle<CandlProgram>, CandlGraphicsError)>,
         //
         draw_fun: Option<F>,
         _state: PhantomData<S>,
@@ -210,11 +1074,22 @@ mod candl_graphics {
     where F: CandlGraphicsDrawer<S, M, O>, S: CandlUpdate<M> {
         fn init() -> CandlGraphics<F, S, M, O> {
             Self {
+                gl: None,
                 clear_color: [0.0, 0.0, 0.0, 1.0],
                 size: (0, 0),
                 scale_factor: 0.0,
-                shaders: vec!(),
-                programs: vec!(),
+                shaders: CandlSlotMap::new("shader"),
+                programs: CandlSlotMap::new("program"),
+                buffers: CandlSlotMap::new("buffer"),
+                vertex_arrays: CandlSlotMap::new("vertex array"),
+                textures: CandlSlotMap::new("texture"),
+                render_targets: CandlSlotMap::new("render target"),
+                frame_target: None,
+                preprocessor: CandlPreprocessor::default(),
+                watched: vec!(),
+                reload_interval: None,
+                last_check: Instant::now(),
+                reload_errors: vec!(),
                 draw_fun: None,
                 _state: PhantomData,
                 _message: PhantomData,
@@ -222,7 +1097,28 @@ mod candl_graphics {
             }
         }
 
-        fn finalize(&mut self) {}
+        fn setup(&mut self, ctx: &CandlContext) -> Result<(), CandlError> {
+            self.gl = Some(ctx.gl().clone());
+            Ok(())
+        }
+
+        fn teardown(&mut self, _ctx: &CandlContext) {
+            // the resources hold the functions of the context
+            for vertex_array in self.vertex_arrays.clear() { vertex_array.delete(); }
+            for target in self.render_targets.clear() { target.delete(); }
+            for texture in self.textures.clear() { texture.delete(); }
+            for buffer in self.buffers.clear() { buffer.delete(); }
+            for program in self.programs.clear() { program.delete(); }
+            for shader in self.shaders.clear() { shader.delete(); }
+            self.forget_resources();
+            self.gl = None;
+        }
+
+        fn context_recreated(&mut self, ctx: &CandlContext) -> Result<(), CandlError> {
+            // the resources are gone with the previous context
+            self.forget_resources();
+            self.setup(ctx)
+        }
 
         fn set_scale_factor(&mut self, scale_factor: f64) {
             self.scale_factor = scale_factor;
@@ -231,78 +1127,510 @@ mod candl_graphics {
         fn set_size(&mut self, nsize: (u32, u32)) { self.size = nsize; }
 
         fn draw_frame(&mut self, state: &S) {
-            unsafe {
-                gl::ClearColor(
-                    self.clear_color[0], self.clear_color[1],
-                    self.clear_color[2], self.clear_color[3]
-                );
-                gl::Clear(gl::COLOR_BUFFER_BIT);
+            let gl = match &self.gl {
+                Some(gl) => gl.clone(),
+                None => return
+            };
+            if let Some(interval) = self.reload_interval {
+                if self.last_check.elapsed() >= interval {
+                    // a program failing again replaces its previous error
+                    for (program, err) in self.reload_programs() {
+                        self.reload_errors.retain(|(handle, _)| *handle != program);
+                        self.reload_errors.push((program, err));
+                    }
+                }
             }
-            if let Some(fun) = &self.draw_fun {
-                fun.execute(Some(state), None);
+            let target = match self.frame_target {
+                Some(handle) => self.render_targets.get_mut(handle).ok(),
+                None => None
+            };
+            match target {
+                Some(target) => {
+                    target.bind();
+                    clear(&gl, self.clear_color, gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT | gl::STENCIL_BUFFER_BIT);
+                    if let Some(fun) = &self.draw_fun {
+                        fun.execute(Some(state), None);
+                    }
+                    target.resolve();
+                    // checked by set_frame_target(), the target has a color attachment
+                    target.blit(0, self.size);
+                    unsafe { gl.Viewport(0, 0, self.size.0 as GLsizei, self.size.1 as GLsizei); }
+                }
+                None => {
+                    clear(&gl, self.clear_color, gl::COLOR_BUFFER_BIT);
+                    if let Some(fun) = &self.draw_fun {
+                        fun.execute(Some(state), None);
+                    }
+                }
             }
         }
     }
 
+    /// clear the bound framebuffer
+    fn clear(gl: &CandlGl, color: [f32; 4], mask: GLenum) {
+        unsafe {
+            gl.ClearColor(color[0], color[1], color[2], color[3]);
+            gl.Clear(mask);
+        }
+    }
+
     impl<F, S, M, O> CandlGraphics<F, S, M, O>
     where F: CandlGraphicsDrawer<S, M, O>, S: CandlUpdate<M> {
+        /// get the functions of the context, once the renderer is set up
+        fn gl(&self) -> Result<CandlGl, CandlGraphicsError> {
+            self.gl.clone().ok_or(CandlGraphicsError::NoContext)
+        }
+
+        /// empty the slot maps, without releasing the resources, so all the
+        /// handles become stale
+        fn forget_resources(&mut self) {
+            self.shaders.clear();
+            self.programs.clear();
+            self.buffers.clear();
+            self.vertex_arrays.clear();
+            self.textures.clear();
+            self.render_targets.clear();
+            self.frame_target = None;
+            self.watched.clear();
+            self.reload_errors.clear();
+        }
+
         /// redefine the drawing closure
         pub fn set_draw_fun(&mut self, draw_fun: F) { self.draw_fun = Some(draw_fun); }
 
+        /// set the preprocessor used by CandlGraphics to compile the shaders
+        pub fn set_preprocessor(&mut self, preprocessor: CandlPreprocessor) {
+            self.preprocessor = preprocessor;
+        }
+
+        /// get a reference to the preprocessor
+        pub fn get_preprocessor(&self) -> &CandlPreprocessor { &self.preprocessor }
+
         /// generate a new shader and save it in CandlGraphics
         pub fn gen_shader(&mut self, variant: CandlShaderVariant, src: &str)
-        -> Result<usize, CandlGraphicsError> {
-            match CandlShader::new(variant, src) {
-                Ok(shader) => Ok(self.add_shader(shader)),
-                Err(err) => Err(CandlGraphicsError::ShaderError(err))
-            }
+        -> Result<CandlHandle<CandlShader>, CandlGraphicsError> {
+            let shader = CandlShader::new_with_preprocessor(&self.gl()?, variant, src, &self.preprocessor)?;
+            Ok(self.add_shader(shader))
         }
 
         /// adding a shader to CandlGraphics
-        pub fn add_shader(&mut self, shader: CandlShader) -> usize {
-            self.shaders.push(shader);
-            self.shaders.len() - 1
+        pub fn add_shader(&mut self, shader: CandlShader) -> CandlHandle<CandlShader> {
+            self.shaders.insert(shader)
         }
 
         /// get a reference to a shader
-        pub fn get_shader(&self, id: usize) -> &CandlShader { &self.shaders[id] }
+        pub fn get_shader(&self, handle: CandlHandle<CandlShader>)
+        -> Result<&CandlShader, CandlGraphicsError> {
+            self.shaders.get(handle)
+        }
+
+        /// remove a shader and delete it, the context must be current
+        pub fn remove_shader(&mut self, handle: CandlHandle<CandlShader>)
+        -> Result<(), CandlGraphicsError> {
+            self.shaders.remove(handle)?.delete();
+            Ok(())
+        }
+
+        /// generate a new program, from the handles of shaders in
+        /// CandlGraphics, so there is no need to generate shaders outside
+        /// CandlGraphics, just push the handles `gen_shader` and `add_shader`
+        /// return.
+        pub fn gen_program(
+            &mut self,
+            fs: Option<CandlHandle<CandlShader>>,
+            vs: Option<CandlHandle<CandlShader>>
+        ) -> Result<CandlHandle<CandlProgram>, CandlGraphicsError> {
+            let fs = fs.map(|handle| self.get_shader(handle)).transpose()?;
+            let vs = vs.map(|handle| self.get_shader(handle)).transpose()?;
+            let program = CandlProgram::new(&self.gl()?, fs, vs)?;
+            Ok(self.add_program(program))
+        }
+
+        /// generate a new program with any stages, from the handles of the
+        /// shaders in CandlGraphics, see `CandlProgram::from_shaders()`
+        pub fn gen_program_from_shaders(&mut self, shaders: &[CandlHandle<CandlShader>])
+        -> Result<CandlHandle<CandlProgram>, CandlGraphicsError> {
+            let shaders = shaders.iter()
+                .map(|handle| self.get_shader(*handle))
+                .collect::<Result<Vec<&CandlShader>, CandlGraphicsError>>()?;
+            let program = CandlProgram::from_shaders(&self.gl()?, &shaders)?;
+            Ok(self.add_program(program))
+        }
 
-        /// removing a shader
-        pub fn remove_shader(&mut self, id: usize) -> CandlShader {
-            self.shaders.remove(id)
+        /// generate a new program from its description, compiling its shaders
+        pub fn gen_program_from_desc(&mut self, desc: &CandlProgramDesc)
+        -> Result<CandlHandle<CandlProgram>, CandlGraphicsError> {
+            let program = CandlProgram::from_desc_with_preprocessor(&self.gl()?, desc, &self.preprocessor)?;
+            Ok(self.add_program(program))
         }
 
-        /// generate a new program, the usize in the Option is the index inside
-        /// the shaders vector in CandlGraphics, so there is no need to
-        /// generate shaders outside CandlGraphics, just push the id the
-        /// `gen_shaders` and `add_shaders` returns.
-        pub fn gen_program(&mut self, fs: Option<usize>, vs: Option<usize>)
-        -> Result<usize, CandlGraphicsError> {
-            let fs = if let Some(sh) = fs { Some(self.get_shader(sh)) } else { None };
-            let vs = if let Some(sh) = vs { Some(self.get_shader(sh)) } else { None };
-            match CandlProgram::new(fs, vs) {
-                Ok(program) => Ok(self.add_program(program)),
-                Err(err) => Err(CandlGraphicsError::ProgramError(err))
+        /// generate a permutation of a program for each set of defines, added
+        /// to the ones of the preprocessor, and get their handles in the same
+        /// order
+        pub fn gen_program_permutations(
+            &mut self,
+            desc: &CandlProgramDesc,
+            permutations: &[&[(&str, &str)]]
+        ) -> Result<Vec<CandlHandle<CandlProgram>>, CandlGraphicsError> {
+            let gl = self.gl()?;
+            let mut programs = Vec::with_capacity(permutations.len());
+            for defines in permutations {
+                let preprocessor = self.preprocessor.permutation(defines);
+                match CandlProgram::from_desc_with_preprocessor(&gl, desc, &preprocessor) {
+                    Ok(program) => programs.push(program),
+                    Err(err) => {
+                        for program in programs { program.delete(); }
+                        return Err(err);
+                    }
+                }
             }
+            Ok(programs.into_iter().map(|program| self.add_program(program)).collect())
         }
 
         /// add a program in CandlGraphics
-        pub fn add_program(&mut self, program: CandlProgram) -> usize {
-            self.programs.push(program);
-            self.programs.len() - 1
+        pub fn add_program(&mut self, program: CandlProgram) -> CandlHandle<CandlProgram> {
+            self.programs.insert(program)
         }
 
         /// get a reference to a program
-        pub fn get_program(&self, id: usize) -> &CandlProgram { &self.programs[id] }
+        pub fn get_program(&self, handle: CandlHandle<CandlProgram>)
+        -> Result<&CandlProgram, CandlGraphicsError> {
+            self.programs.get(handle)
+        }
+
+        /// remove a program and delete it, the context must be current, and
+        /// stop watching its files
+        pub fn remove_program(&mut self, handle: CandlHandle<CandlProgram>)
+        -> Result<(), CandlGraphicsError> {
+            self.programs.remove(handle)?.delete();
+            self.watched.retain(|watched| watched.program != handle);
+            Ok(())
+        }
+
+        /// load a program from the files of its stages, and watch them
+        ///
+        /// When a file changes, the program is rebuilt by `reload_programs()`,
+        /// or during the next frame once the hot reload is enabled with
+        /// `set_hot_reload()`. If the new sources don't compile or link, the
+        /// last good program is kept, and the build is tried again at the
+        /// next check.
+        pub fn load_program<P: AsRef<Path>>(&mut self, files: &[(CandlShaderVariant, P)])
+        -> Result<CandlHandle<CandlProgram>, CandlGraphicsError> {
+            let files: Vec<(CandlShaderVariant, PathBuf, Option<SystemTime>)> = files.iter()
+                .map(|(variant, path)| (*variant, path.as_ref().to_path_buf(), None))
+                .collect();
+            let gl = self.gl()?;
+            let (desc, mtimes) = CandlWatchedProgram::load(&files)?;
+            let program = CandlProgram::from_desc_with_preprocessor(&gl, &desc, &self.preprocessor)?;
+            let program = self.add_program(program);
+            let mut watched = CandlWatchedProgram { program, files };
+            watched.built(mtimes);
+            self.watched.push(watched);
+            Ok(program)
+        }
+
+        /// set how often the files of the loaded programs are checked while
+        /// drawing the frames, or disable the hot reload with `None` (the
+        /// default)
+        pub fn set_hot_reload(&mut self, interval: Option<Duration>) {
+            self.reload_interval = interval;
+        }
+
+        /// check now the files of the loaded programs, and rebuild the ones
+        /// which changed
+        ///
+        /// The context must be the current one. A program which fails to
+        /// build is kept as it was, its error is returned with its handle, and
+        /// the build is tried again at the next check.
+        /// Nothing is reloaded before the renderer is set up.
+        pub fn reload_programs(&mut self) -> Vec<(CandlHandle<CandlProgram>, CandlGraphicsError)> {
+            self.last_check = Instant::now();
+            let gl = match &self.gl {
+                Some(gl) => gl.clone(),
+                None => return vec!()
+            };
+            let mut errors = vec!();
+            for watched in self.watched.iter_mut().filter(|watched| watched.changed()) {
+                let preprocessor = &self.preprocessor;
+                let res = CandlWatchedProgram::load(&watched.files).and_then(|(desc, mtimes)| {
+                    let program = CandlProgram::from_desc_with_preprocessor(&gl, &desc, preprocessor)?;
+                    Ok((program, mtimes))
+                });
+                match res {
+                    Ok((program, mtimes)) => match self.programs.get_mut(watched.program) {
+                        Ok(current) => {
+                            let old = std::mem::replace(current, program);
+                            old.delete();
+                            watched.built(mtimes);
+                        }
+                        Err(err) => {
+                            program.delete();
+                            errors.push((watched.program, err));
+                        }
+                    },
+                    Err(err) => errors.push((watched.program, err))
+                }
+            }
+            errors
+        }
+
+        /// get the errors of the reloads done while drawing the frames, since
+        /// the last call, keeping only the last one of each program
+        pub fn take_reload_errors(&mut self) -> Vec<(CandlHandle<CandlProgram>, CandlGraphicsError)> {
+            std::mem::take(&mut self.reload_errors)
+        }
+
+        /// set the value of a uniform of a program, see
+        /// `CandlProgram::set_uniform()`
+        pub fn set_uniform<T: CandlUniform>(
+            &mut self,
+            program: CandlHandle<CandlProgram>,
+            name: &str,
+            value: T
+        ) -> Result<(), CandlGraphicsError> {
+            self.programs.get_mut(program)?.set_uniform(name, value)
+        }
+
+        /// generate a new vertex buffer and save it in CandlGraphics
+        pub fn gen_vertex_buffer<T: Copy>(&mut self, usage: CandlBufferUsage, data: &[T])
+        -> Result<CandlHandle<CandlBuffer>, CandlGraphicsError> {
+            Ok(self.add_buffer(CandlBuffer::vertex(&self.gl()?, usage, data)))
+        }
+
+        /// generate a new index buffer and save it in CandlGraphics
+        pub fn gen_index_buffer<I: CandlIndex>(&mut self, usage: CandlBufferUsage, indices: &[I])
+        -> Result<CandlHandle<CandlBuffer>, CandlGraphicsError> {
+            Ok(self.add_buffer(CandlBuffer::index(&self.gl()?, usage, indices)))
+        }
+
+        /// add a buffer in CandlGraphics
+        pub fn add_buffer(&mut self, buffer: CandlBuffer) -> CandlHandle<CandlBuffer> {
+            self.buffers.insert(buffer)
+        }
+
+        /// get a reference to a buffer
+        pub fn get_buffer(&self, handle: CandlHandle<CandlBuffer>)
+        -> Result<&CandlBuffer, CandlGraphicsError> {
+            self.buffers.get(handle)
+        }
+
+        /// get a mutable reference to a buffer, to update its data
+        pub fn get_buffer_mut(&mut self, handle: CandlHandle<CandlBuffer>)
+        -> Result<&mut CandlBuffer, CandlGraphicsError> {
+            self.buffers.get_mut(handle)
+        }
+
+        /// remove a buffer and delete it, the context must be current
+        pub fn remove_buffer(&mut self, handle: CandlHandle<CandlBuffer>)
+        -> Result<(), CandlGraphicsError> {
+            self.buffers.remove(handle)?.delete();
+            Ok(())
+        }
+
+        /// generate a new vertex array for a program, from the handles of the
+        /// buffers in CandlGraphics, see `CandlVertexArray::new()`
+        pub fn gen_vertex_array(
+            &mut self,
+            program: CandlHandle<CandlProgram>,
+            buffers: &[(CandlHandle<CandlBuffer>, &CandlVertexLayout)],
+            index: Option<CandlHandle<CandlBuffer>>
+        ) -> Result<CandlHandle<CandlVertexArray>, CandlGraphicsError> {
+            let buffers = buffers.iter()
+                .map(|(handle, layout)| Ok((self.get_buffer(*handle)?, *layout)))
+                .collect::<Result<Vec<(&CandlBuffer, &CandlVertexLayout)>, CandlGraphicsError>>()?;
+            let index = index.map(|handle| self.get_buffer(handle)).transpose()?;
+            let vertex_array = CandlVertexArray::new(&self.gl()?, self.get_program(program)?, &buffers, index)?;
+            Ok(self.add_vertex_array(vertex_array))
+        }
+
+        /// add a vertex array in CandlGraphics
+        pub fn add_vertex_array(&mut self, vertex_array: CandlVertexArray)
+        -> CandlHandle<CandlVertexArray> {
+            self.vertex_arrays.insert(vertex_array)
+        }
+
+        /// get a reference to a vertex array
+        pub fn get_vertex_array(&self, handle: CandlHandle<CandlVertexArray>)
+        -> Result<&CandlVertexArray, CandlGraphicsError> {
+            self.vertex_arrays.get(handle)
+        }
+
+        /// get a mutable reference to a vertex array
+        pub fn get_vertex_array_mut(&mut self, handle: CandlHandle<CandlVertexArray>)
+        -> Result<&mut CandlVertexArray, CandlGraphicsError> {
+            self.vertex_arrays.get_mut(handle)
+        }
+
+        /// remove a vertex array and delete it, the context must be current
+        pub fn remove_vertex_array(&mut self, handle: CandlHandle<CandlVertexArray>)
+        -> Result<(), CandlGraphicsError> {
+            self.vertex_arrays.remove(handle)?.delete();
+            Ok(())
+        }
+
+        /// generate a new texture and save it in CandlGraphics, see
+        /// `CandlTexture::new()`
+        pub fn gen_texture<T: Copy>(
+            &mut self,
+            kind: CandlTextureKind,
+            format: CandlTextureFormat,
+            size: (u32, u32),
+            data: Option<&[T]>
+        ) -> Result<CandlHandle<CandlTexture>, CandlGraphicsError> {
+            Ok(self.add_texture(CandlTexture::new(&self.gl()?, kind, format, size, data)?))
+        }
+
+        /// decode an image file in a new texture, and save it in
+        /// CandlGraphics, see `CandlTexture::from_image()`
+        #[cfg(feature = "image")]
+        pub fn load_texture<P: AsRef<Path>>(&mut self, path: P, srgb: bool)
+        -> Result<CandlHandle<CandlTexture>, CandlGraphicsError> {
+            Ok(self.add_texture(CandlTexture::from_image(&self.gl()?, path, srgb)?))
+        }
+
+        /// add a texture in CandlGraphics
+        pub fn add_texture(&mut self, texture: CandlTexture) -> CandlHandle<CandlTexture> {
+            self.textures.insert(texture)
+        }
+
+        /// get a reference to a texture
+        pub fn get_texture(&self, handle: CandlHandle<CandlTexture>)
+        -> Result<&CandlTexture, CandlGraphicsError> {
+            self.textures.get(handle)
+        }
+
+        /// get a mutable reference to a texture, to update it
+        pub fn get_texture_mut(&mut self, handle: CandlHandle<CandlTexture>)
+        -> Result<&mut CandlTexture, CandlGraphicsError> {
+            self.textures.get_mut(handle)
+        }
+
+        /// remove a texture and delete it, the context must be current
+        pub fn remove_texture(&mut self, handle: CandlHandle<CandlTexture>)
+        -> Result<(), CandlGraphicsError> {
+            self.textures.remove(handle)?.delete();
+            Ok(())
+        }
+
+        /// bind a texture to a texture unit
+        pub fn bind_texture(&self, handle: CandlHandle<CandlTexture>, unit: u32)
+        -> Result<(), CandlGraphicsError> {
+            self.get_texture(handle)?.bind(unit);
+            Ok(())
+        }
+
+        /// generate a new render target and save it in CandlGraphics, see
+        /// `CandlRenderTarget::new()`
+        pub fn gen_render_target(
+            &mut self,
+            size: (u32, u32),
+            colors: &[CandlTextureFormat],
+            depth: Option<CandlTextureFormat>,
+            samples: u32
+        ) -> Result<CandlHandle<CandlRenderTarget>, CandlGraphicsError> {
+            Ok(self.add_render_target(CandlRenderTarget::new(&self.gl()?, size, colors, depth, samples)?))
+        }
+
+        /// add a render target in CandlGraphics
+        pub fn add_render_target(&mut self, target: CandlRenderTarget)
+        -> CandlHandle<CandlRenderTarget> {
+            self.render_targets.insert(target)
+        }
+
+        /// get a reference to a render target, to sample its textures
+        pub fn get_render_target(&self, handle: CandlHandle<CandlRenderTarget>)
+        -> Result<&CandlRenderTarget, CandlGraphicsError> {
+            self.render_targets.get(handle)
+        }
+
+        /// get a mutable reference to a render target
+        pub fn get_render_target_mut(&mut self, handle: CandlHandle<CandlRenderTarget>)
+        -> Result<&mut CandlRenderTarget, CandlGraphicsError> {
+            self.render_targets.get_mut(handle)
+        }
 
-        /// remove a program
-        pub fn remove_program(&mut self, id: usize) -> CandlProgram {
-            self.programs.remove(id)
+        /// remove a render target and delete it, the context must be current,
+        /// it stops being the frame target if it was
+        pub fn remove_render_target(&mut self, handle: CandlHandle<CandlRenderTarget>)
+        -> Result<(), CandlGraphicsError> {
+            self.render_targets.remove(handle)?.delete();
+            if self.frame_target == Some(handle) { self.frame_target = None; }
+            Ok(())
+        }
+
+        /// render the frames into a render target, then copy its first color
+        /// attachment to the window, or render them directly in the window
+        /// with `None`
+        ///
+        /// The target is rejected if it has no color attachment.
+        pub fn set_frame_target(&mut self, target: Option<CandlHandle<CandlRenderTarget>>)
+        -> Result<(), CandlGraphicsError> {
+            if let Some(handle) = target {
+                if self.get_render_target(handle)?.color_texture(0).is_none() {
+                    return Err(CandlGraphicsError::FramebufferError(
+                        String::from("the frame target has no color attachment")
+                    ));
+                }
+            }
+            self.frame_target = target;
+            Ok(())
+        }
+
+        /// get the render target of the frames, if any
+        pub fn frame_target(&self) -> Option<CandlHandle<CandlRenderTarget>> { self.frame_target }
+
+        /// render a drawer into a render target, cleared with the clear color,
+        /// and resolve it, to sample its textures later
+        ///
+        /// The default framebuffer and the viewport of the window are restored
+        /// afterwards.
+        pub fn draw_to_target<D>(
+            &mut self,
+            target: CandlHandle<CandlRenderTarget>,
+            drawer: &D,
+            state: Option<&S>,
+            opts: Option<&O>
+        ) -> Result<(), CandlGraphicsError>
+        where D: CandlGraphicsDrawer<S, M, O> {
+            let gl = self.gl()?;
+            let target = self.render_targets.get_mut(target)?;
+            target.bind();
+            clear(&gl, self.clear_color, gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT | gl::STENCIL_BUFFER_BIT);
+            drawer.execute(state, opts);
+            target.resolve();
+            unsafe { gl.Viewport(0, 0, self.size.0 as GLsizei, self.size.1 as GLsizei); }
+            Ok(())
+        }
+
+        /// draw a vertex array with a program
+        pub fn draw(
+            &self,
+            program: CandlHandle<CandlProgram>,
+            vertex_array: CandlHandle<CandlVertexArray>,
+            primitive: CandlPrimitive
+        ) -> Result<(), CandlGraphicsError> {
+            self.get_vertex_array(vertex_array)?.draw(self.get_program(program)?, primitive);
+            Ok(())
+        }
+
+        /// draw several instances of a vertex array with a program
+        pub fn draw_instanced(
+            &self,
+            program: CandlHandle<CandlProgram>,
+            vertex_array: CandlHandle<CandlVertexArray>,
+            primitive: CandlPrimitive,
+            instances: usize
+        ) -> Result<(), CandlGraphicsError> {
+            self.get_vertex_array(vertex_array)?
+                .draw_instanced(self.get_program(program)?, primitive, instances);
+            Ok(())
         }
 
         /// make use of a program already added
-        pub fn use_program(&self,id: usize) {
-            unsafe { gl::UseProgram(self.get_program(id).get_ptr()); }
+        pub fn use_program(&self, handle: CandlHandle<CandlProgram>) -> Result<(), CandlGraphicsError> {
+            let gl = self.gl()?;
+            unsafe { gl.UseProgram(self.get_program(handle)?.get_ptr()); }
+            Ok(())
         }
 
         /// apply the clear color
@@ -310,4 +1638,185 @@ mod candl_graphics {
             self.clear_color = new_color;
         }
     }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn diagnostic(source: Option<&str>, line: Option<u32>, message: &str) -> CandlShaderDiagnostic {
+            CandlShaderDiagnostic {
+                source: source.map(String::from),
+                line,
+                message: String::from(message)
+            }
+        }
+
+        #[test]
+        fn parse_the_locations_of_each_driver() {
+            assert_eq!(
+                CandlShaderDiagnostic::parse_location("0:12(3): error: `color' undeclared"),
+                Some((0, 12, String::from("error: `color' undeclared")))
+            );
+            assert_eq!(
+                CandlShaderDiagnostic::parse_location("0(12) : error C1008: undefined variable \"color\""),
+                Some((0, 12, String::from("error C1008: undefined variable \"color\"")))
+            );
+            assert_eq!(
+                CandlShaderDiagnostic::parse_location("ERROR: 0:12: 'color' : undeclared identifier"),
+                Some((0, 12, String::from("error: 'color' : undeclared identifier")))
+            );
+            assert_eq!(
+                CandlShaderDiagnostic::parse_location("WARNING: 2:7: 'unused' : unused variable"),
+                Some((2, 7, String::from("warning: 'unused' : unused variable")))
+            );
+        }
+
+        #[test]
+        fn keep_the_lines_without_location() {
+            assert_eq!(CandlShaderDiagnostic::parse_location("ERROR: 1 compilation errors."), None);
+            assert_eq!(CandlShaderDiagnostic::parse_location("warning: extension not supported"), None);
+            assert_eq!(CandlShaderDiagnostic::parse_location("0:x: error"), None);
+            assert_eq!(CandlShaderDiagnostic::parse_location("0(12 : error"), None);
+        }
+
+        #[test]
+        fn parse_a_log_with_included_files() {
+            let sources = vec![String::from("common.glsl"), String::from("lights.glsl")];
+            let log = "0:4(10): error: syntax error\n\
+                \n\
+                ERROR: 2:7: 'light' : undeclared identifier\n\
+                ERROR: 3:1: source out of the included files\n\
+                0:0(1): warning: no line\n\
+                ERROR: 2 compilation errors.  No code generated.\n";
+            assert_eq!(CandlShaderDiagnostic::parse_log(log, &sources), vec![
+                diagnostic(None, Some(4), "error: syntax error"),
+                diagnostic(Some("lights.glsl"), Some(7), "error: 'light' : undeclared identifier"),
+                diagnostic(None, None, "ERROR: 3:1: source out of the included files"),
+                diagnostic(None, None, "warning: no line"),
+                diagnostic(None, None, "ERROR: 2 compilation errors.  No code generated.")
+            ]);
+        }
+
+        #[test]
+        fn keep_the_locations_in_unknown_sources() {
+            let log = "0(3) : error C0000: syntax error
+1(5) : error C1008: undefined variable \"light\"";
+            assert_eq!(CandlShaderDiagnostic::parse_log(log, &[]), vec![
+                diagnostic(None, Some(3), "error C0000: syntax error"),
+                diagnostic(None, None, "1(5) : error C1008: undefined variable \"light\"")
+            ]);
+        }
+
+        fn preprocess_error(res: Result<(String, Vec<String>), CandlGraphicsError>) -> String {
+            match res {
+                Err(CandlGraphicsError::PreprocessError(message)) => message,
+                res => panic!("expected a preprocess error, got {:?}", res)
+            }
+        }
+
+        #[test]
+        fn detect_the_version_after_blank_lines() {
+            let pre = CandlPreprocessor::new().clear_extensions();
+            let (out, sources) = pre.process(
+                CandlShaderVariant::VertexShader,
+                "\n  \n#version 150\nvoid main() {}"
+            ).unwrap();
+            assert_eq!(out, "#version 150 core\n#define CANDL_VERTEX_SHADER\n#line 3 0\nvoid main() {}\n");
+            assert!(sources.is_empty());
+            let (out, _) = pre.process(CandlShaderVariant::FragmentShader, "\n#version 300 es\n").unwrap();
+            assert!(out.starts_with("#version 300 es\n"));
+            let (out, _) = pre.process(CandlShaderVariant::FragmentShader, "#version 100\n").unwrap();
+            assert!(out.starts_with("#version 100\n"));
+            assert_eq!(
+                preprocess_error(pre.process(CandlShaderVariant::VertexShader, "\n#version core\n")),
+                "line 2: bad version directive"
+            );
+        }
+
+        #[test]
+        fn inject_the_extensions_and_the_defines() {
+            let pre = CandlPreprocessor::new()
+                .add_extension("GL_ARB_shading_language_420pack", "enable")
+                .define("LIGHTS", "2")
+                .define("LIGHTS", "4")
+                .define_for(CandlShaderVariant::VertexShader, "SKINNING", "1")
+                .define_for(CandlShaderVariant::FragmentShader, "SHADOWS", "1");
+            let (out, _) = pre.process(CandlShaderVariant::FragmentShader, "void main() {}").unwrap();
+            assert_eq!(out, "#version 330 core\n\
+                #extension GL_ARB_separate_shader_objects : require\n\
+                #extension GL_ARB_shading_language_420pack : enable\n\
+                #define CANDL_FRAGMENT_SHADER\n\
+                #define LIGHTS 4\n\
+                #define SHADOWS 1\n\
+                #line 1 0\n\
+                void main() {}\n");
+        }
+
+        #[test]
+        fn number_the_lines_for_each_version() {
+            let line = |pre: CandlPreprocessor| {
+                let (out, _) = pre.clear_extensions()
+                    .process(CandlShaderVariant::VertexShader, "void main() {}")
+                    .unwrap();
+                out.lines().find(|line| line.starts_with("#line")).map(String::from)
+            };
+            let pre = CandlPreprocessor::new();
+            assert_eq!(line(pre.clone().set_version(Some(150))), Some(String::from("#line 0 0")));
+            assert_eq!(line(pre.clone().set_version(Some(330))), Some(String::from("#line 1 0")));
+            let pre = pre.set_profile(CandlGlslProfile::Es);
+            assert_eq!(line(pre.clone().set_version(Some(100))), Some(String::from("#line 0 0")));
+            assert_eq!(line(pre.set_version(Some(300))), Some(String::from("#line 1 0")));
+        }
+
+        #[test]
+        fn expand_the_includes() {
+            let pre = CandlPreprocessor::new()
+                .clear_extensions()
+                .add_file("common.glsl", "float a;\n#include <lights.glsl>\nfloat b;")
+                .add_file("lights.glsl", "float c;");
+            let (out, sources) = pre.process(
+                CandlShaderVariant::VertexShader,
+                "#version 330\n#include \"common.glsl\"\nvoid main() {}"
+            ).unwrap();
+            assert_eq!(out, "#version 330 core\n\
+                #define CANDL_VERTEX_SHADER\n\
+                #line 2 0\n\
+                #line 1 1\n\
+                float a;\n\
+                #line 1 2\n\
+                float c;\n\
+                #line 3 1\n\
+                float b;\n\
+                #line 3 0\n\
+                void main() {}\n");
+            assert_eq!(sources, vec![String::from("common.glsl"), String::from("lights.glsl")]);
+        }
+
+        #[test]
+        fn reject_the_bad_includes() {
+            let pre = CandlPreprocessor::new()
+                .add_file("a.glsl", "#include \"b.glsl\"")
+                .add_file("b.glsl", "\n#include \"a.glsl\"");
+            let process = |src| pre.process(CandlShaderVariant::VertexShader, src);
+            assert_eq!(
+                preprocess_error(process("#include \"a.glsl\"")),
+                "b.glsl, line 2: recursive include of \"a.glsl\""
+            );
+            assert_eq!(
+                preprocess_error(process("\n#include \"c.glsl\"")),
+                "source, line 2: unknown include file \"c.glsl\""
+            );
+            assert_eq!(preprocess_error(process("#include c.glsl")), "source, line 1: bad include directive");
+        }
+
+        #[test]
+        fn parse_the_include_directives() {
+            assert_eq!(include_name("#include \"common.glsl\""), Some(Some("common.glsl")));
+            assert_eq!(include_name("  #include <common.glsl>  "), Some(Some("common.glsl")));
+            assert_eq!(include_name("#include common.glsl"), Some(None));
+            assert_eq!(include_name("#include \"\""), Some(None));
+            assert_eq!(include_name("#include \"common.glsl>"), Some(None));
+            assert_eq!(include_name("float include;"), None);
+        }
+    }
 }
@@ -0,0 +1,9 @@
+// ===========================================================
+// derive macro errors tests
+// ===========================================================
+
+#[test]
+fn reject_bad_vertices() {
+    let cases = trybuild::TestCases::new();
+    cases.compile_fail("tests/ui/*.rs");
+}
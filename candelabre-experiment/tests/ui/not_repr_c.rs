@@ -0,0 +1,8 @@
+use candelabre_experiment::CandlVertex;
+
+#[derive(Clone, Copy, CandlVertex)]
+struct Vertex {
+    pos: [f32; 2]
+}
+
+fn main() {}
@@ -0,0 +1,7 @@
+use candelabre_experiment::CandlVertex;
+
+#[repr(C)]
+#[derive(Clone, Copy, CandlVertex)]
+struct Vertex([f32; 2], [f32; 3]);
+
+fn main() {}
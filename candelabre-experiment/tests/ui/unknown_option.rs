@@ -0,0 +1,17 @@
+use candelabre_experiment::CandlVertex;
+
+#[repr(C)]
+#[derive(Clone, Copy, CandlVertex)]
+#[candl(stride = 12)]
+struct Vertex {
+    pos: [f32; 2]
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, CandlVertex)]
+struct Instance {
+    #[candl(offset = 4)]
+    offset: [f32; 3]
+}
+
+fn main() {}
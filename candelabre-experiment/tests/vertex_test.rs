@@ -0,0 +1,45 @@
+use candelabre_experiment::*;
+
+// ===========================================================
+// struct for the test
+// ===========================================================
+
+#[repr(C)]
+#[derive(Clone, Copy, CandlVertex)]
+struct Vertex {
+    #[candl(name = "co")]
+    pos: [f32; 2],
+    #[candl(normalized)]
+    color: [u8; 3],
+    #[candl(skip)]
+    _padding: u8,
+    #[candl(location = 3)]
+    id: u32
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, CandlVertex)]
+#[candl(divisor = 1)]
+struct Instance {
+    offset: [f32; 3]
+}
+
+// ===========================================================
+// vertex layout tests
+// ===========================================================
+
+#[test]
+fn derive_vertex_layout() -> Result<(), String> {
+    let layout = Vertex::layout();
+    let expected = CandlVertexLayout::of::<Vertex>()
+        .attribute::<[f32; 2], _>("co", 0)
+        .normalized_attribute::<[u8; 3], _>("color", 8)
+        .attribute_with(CandlVertexAttrib::new("id", u32::format(), 12).set_location(3));
+    if layout != expected {
+        Err(format!("Test failed: wrong layout {:?}", layout))
+    } else if layout.stride() != 16 {
+        Err(format!("Test failed: stride of {} bytes instead of 16", layout.stride()))
+    } else if Instance::layout().divisor() != 1 {
+        Err(String::from("Test failed: divisor not set"))
+    } else { Ok(()) }
+}
@@ -3,7 +3,7 @@
 //! 
 //! ???
 
-use candelabre_windowing::{CandlRenderer, CandlUpdate};
+use candelabre_windowing::{CandlContext, CandlError, CandlRenderer, CandlUpdate};
 
 pub struct SurfaceState();
 
@@ -20,9 +20,10 @@ impl CandlRenderer<CandlRender, SurfaceState, ()> for CandlRender {
         CandlRender {}
     }
 
-    fn finalize(&mut self) {
+    fn setup(&mut self, _ctx: &CandlContext) -> Result<(), CandlError> {
         //
         //
+        Ok(())
     }
 
     fn set_scale_factor(&mut self, scale_factor: f64) {
@@ -0,0 +1,214 @@
+//! OpenGL debug output
+//!
+//! When the debug option is set in `CandlOptions`, each surface requests a
+//! debug context and installs a `KHR_debug` callback on it. If the driver
+//! doesn't support `KHR_debug`, the surface falls back to polling
+//! `glGetError` after each frame. The messages go to the `log` facade, or to a
+//! user handler if one is set.
+
+use crate::candl_gl::{self as gl, CandlGl};
+use crate::candl_gl::types::{GLchar, GLenum, GLsizei, GLuint};
+use glutin::window::WindowId;
+use log::{debug, error, info, warn};
+use std::fmt;
+use std::os::raw::c_void;
+use std::slice;
+
+/// Severity of an OpenGL debug message
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum CandlGlSeverity {
+    /// notification, not an issue (routed to `debug!`)
+    Notification,
+    /// minor performance warning or redundant state change (routed to `info!`)
+    Low,
+    /// major performance warning or deprecated behavior (routed to `warn!`)
+    Medium,
+    /// error or undefined behavior (routed to `error!`)
+    High
+}
+
+impl CandlGlSeverity {
+    fn from_glenum(severity: GLenum) -> Self {
+        match severity {
+            gl::DEBUG_SEVERITY_HIGH => CandlGlSeverity::High,
+            gl::DEBUG_SEVERITY_MEDIUM => CandlGlSeverity::Medium,
+            gl::DEBUG_SEVERITY_LOW => CandlGlSeverity::Low,
+            _ => CandlGlSeverity::Notification
+        }
+    }
+}
+
+/// OpenGL debug message
+///
+/// A message from the driver, tagged with the window owning the context which
+/// generates it. The source, the type and the id are the raw OpenGL values.
+#[derive(Clone, Debug)]
+pub struct CandlGlMessage {
+    window_id: WindowId,
+    source: GLenum,
+    kind: GLenum,
+    id: GLuint,
+    severity: CandlGlSeverity,
+    message: String
+}
+
+impl CandlGlMessage {
+    /// get the id of the window generating the message
+    pub fn window_id(&self) -> WindowId { self.window_id }
+
+    /// get the source of the message (`GL_DEBUG_SOURCE_*`)
+    pub fn source(&self) -> GLenum { self.source }
+
+    /// get the type of the message (`GL_DEBUG_TYPE_*`)
+    pub fn kind(&self) -> GLenum { self.kind }
+
+    /// get the id of the message, specific to the driver
+    pub fn id(&self) -> GLuint { self.id }
+
+    /// get the severity of the message
+    pub fn severity(&self) -> CandlGlSeverity { self.severity }
+
+    /// get the text of the message
+    pub fn message(&self) -> &str { &self.message }
+}
+
+/// handler receiving the OpenGL debug messages
+pub(crate) type CandlGlDebugHandler = Box<dyn FnMut(&CandlGlMessage)>;
+
+/// the state shared with the driver callback
+struct CandlGlDebugState {
+    window_id: WindowId,
+    handler: Option<CandlGlDebugHandler>
+}
+
+impl CandlGlDebugState {
+    fn dispatch(&mut self, message: CandlGlMessage) {
+        match &mut self.handler {
+            Some(handler) => handler(&message),
+            None => {
+                let id = message.window_id;
+                match message.severity {
+                    CandlGlSeverity::High => error!("[{:?}] OpenGL: {}", id, message.message),
+                    CandlGlSeverity::Medium => warn!("[{:?}] OpenGL: {}", id, message.message),
+                    CandlGlSeverity::Low => info!("[{:?}] OpenGL: {}", id, message.message),
+                    CandlGlSeverity::Notification =>
+                        debug!("[{:?}] OpenGL: {}", id, message.message)
+                }
+            }
+        }
+    }
+}
+
+/// OpenGL debug output
+///
+/// Installed on the current context, it must live as long as the context, and
+/// be dropped after it, because the driver keeps a pointer to it.
+pub struct CandlGlDebug {
+    gl: CandlGl,
+    state: Box<CandlGlDebugState>,
+    callback: bool
+}
+
+impl fmt::Debug for CandlGlDebug {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        f.debug_struct("CandlGlDebug")
+            .field("window_id", &self.state.window_id)
+            .field("callback", &self.callback)
+            .finish()
+    }
+}
+
+impl CandlGlDebug {
+    /// install the debug output on the current context, with its functions
+    ///
+    /// The context should be created with the debug flag, or the driver may
+    /// not generate any message.
+    pub fn install(window_id: WindowId, gl: CandlGl) -> Self {
+        let mut state = Box::new(CandlGlDebugState { window_id, handler: None });
+        let callback = gl.version() >= (4, 3) || gl.has_extension("GL_KHR_debug");
+        if callback {
+            unsafe {
+                gl.Enable(gl::DEBUG_OUTPUT);
+                gl.Enable(gl::DEBUG_OUTPUT_SYNCHRONOUS);
+                gl.DebugMessageCallback(
+                    Some(debug_callback),
+                    &mut *state as *mut CandlGlDebugState as *const c_void
+                );
+            }
+        }
+        CandlGlDebug { gl, state, callback }
+    }
+
+    /// check if the messages come from a `KHR_debug` callback, or from
+    /// polling `glGetError`
+    pub fn uses_callback(&self) -> bool { self.callback }
+
+    /// set the handler receiving the messages, instead of the `log` facade
+    pub fn set_handler<F>(&mut self, handler: F)
+    where F: FnMut(&CandlGlMessage) + 'static {
+        self.state.handler = Some(Box::new(handler));
+    }
+
+    /// send back the messages to the `log` facade
+    pub fn clear_handler(&mut self) { self.state.handler = None; }
+
+    /// move the handler of another debug output to this one
+    pub(crate) fn take_handler_from(&mut self, other: &mut CandlGlDebug) {
+        self.state.handler = other.state.handler.take();
+    }
+
+    /// check the errors with `glGetError`, if there is no callback
+    ///
+    /// The context must be the current one.
+    pub fn poll(&mut self) {
+        if self.callback { return; }
+        loop {
+            let err = unsafe { self.gl.GetError() };
+            if err == gl::NO_ERROR { break; }
+            let name = match err {
+                gl::INVALID_ENUM => "GL_INVALID_ENUM",
+                gl::INVALID_VALUE => "GL_INVALID_VALUE",
+                gl::INVALID_OPERATION => "GL_INVALID_OPERATION",
+                gl::INVALID_FRAMEBUFFER_OPERATION => "GL_INVALID_FRAMEBUFFER_OPERATION",
+                gl::OUT_OF_MEMORY => "GL_OUT_OF_MEMORY",
+                gl::STACK_UNDERFLOW => "GL_STACK_UNDERFLOW",
+                gl::STACK_OVERFLOW => "GL_STACK_OVERFLOW",
+                _ => "unknown error"
+            };
+            let message = CandlGlMessage {
+                window_id: self.state.window_id,
+                source: gl::DEBUG_SOURCE_API,
+                kind: gl::DEBUG_TYPE_ERROR,
+                id: err,
+                severity: CandlGlSeverity::High,
+                message: format!("{} (0x{:04X})", name, err)
+            };
+            self.state.dispatch(message);
+        }
+    }
+}
+
+/// callback called by the driver
+extern "system" fn debug_callback(
+    source: GLenum,
+    kind: GLenum,
+    id: GLuint,
+    severity: GLenum,
+    length: GLsizei,
+    message: *const GLchar,
+    user_param: *mut c_void
+) {
+    let state = unsafe { &mut *(user_param as *mut CandlGlDebugState) };
+    let message = unsafe {
+        let bytes = slice::from_raw_parts(message as *const u8, length.max(0) as usize);
+        String::from_utf8_lossy(bytes).into_owned()
+    };
+    state.dispatch(CandlGlMessage {
+        window_id: state.window_id,
+        source,
+        kind,
+        id,
+        severity: CandlGlSeverity::from_glenum(severity),
+        message
+    });
+}
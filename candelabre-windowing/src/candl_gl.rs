@@ -0,0 +1,89 @@
+//! Per-context OpenGL functions
+//!
+//! Some drivers give function pointers specific to each OpenGL context, so
+//! loading the functions once for the whole application, like the `gl` crate
+//! does, isn't enough when working with several windows. Each surface loads
+//! its own table of functions, the `Gl` structure of this module, shared
+//! through a `CandlGl` handle.
+
+use glutin::{PossiblyCurrent, WindowedContext};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::CStr;
+use std::fmt;
+use std::ops::Deref;
+use std::os::raw::c_void;
+use std::rc::Rc;
+
+#[allow(missing_docs, clippy::all)]
+mod bindings {
+    include!(concat!(env!("OUT_DIR"), "/gl_bindings.rs"));
+}
+
+pub use self::bindings::*;
+
+/// Handle to the OpenGL functions of a context
+///
+/// The handle is cheap to clone, and gives access to the functions through
+/// `Deref`, like `gl.Viewport(0, 0, w, h)`. The functions must only be called
+/// when the context they were loaded from is the current one.
+#[derive(Clone)]
+pub struct CandlGl(Rc<(Gl, HashMap<String, usize>)>);
+
+impl fmt::Debug for CandlGl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "CandlGl")
+    }
+}
+
+impl Deref for CandlGl {
+    type Target = Gl;
+
+    fn deref(&self) -> &Gl { &(self.0).0 }
+}
+
+impl CandlGl {
+    /// load the functions of a context, which must be the current one
+    pub fn load(ctx: &WindowedContext<PossiblyCurrent>) -> Self {
+        let addresses = RefCell::new(HashMap::new());
+        let gl = Gl::load_with(|s| {
+            let ptr = ctx.get_proc_address(s) as *const c_void;
+            addresses.borrow_mut().insert(s.to_string(), ptr as usize);
+            ptr
+        });
+        CandlGl(Rc::new((gl, addresses.into_inner())))
+    }
+
+    /// get the address of a function loaded for the context, or a null
+    /// pointer if it wasn't loaded
+    ///
+    /// Candelabre doesn't load the global functions of the `gl` crate, a
+    /// library relying on them can be loaded from here, with
+    /// `gl::load_with(|s| candl_gl.get_proc_address(s))`.
+    pub fn get_proc_address(&self, name: &str) -> *const c_void {
+        (self.0).1.get(name).map_or(std::ptr::null(), |ptr| *ptr as *const c_void)
+    }
+
+    /// get the version of the context, as (major, minor)
+    pub fn version(&self) -> (u32, u32) {
+        let (mut major, mut minor) = (0, 0);
+        unsafe {
+            self.GetIntegerv(MAJOR_VERSION, &mut major);
+            self.GetIntegerv(MINOR_VERSION, &mut minor);
+        }
+        (major.max(0) as u32, minor.max(0) as u32)
+    }
+
+    /// check if the context supports an extension, like `"GL_KHR_debug"`
+    ///
+    /// Some drivers give a function pointer for any name, so checking if a
+    /// function is loaded doesn't tell if the extension is supported.
+    pub fn has_extension(&self, name: &str) -> bool {
+        let mut count = 0;
+        unsafe { self.GetIntegerv(NUM_EXTENSIONS, &mut count); }
+        (0..count.max(0) as u32).any(|idx| {
+            let ext = unsafe { self.GetStringi(EXTENSIONS, idx) };
+            !ext.is_null() && unsafe { CStr::from_ptr(ext as *const _) }.to_bytes() == name.as_bytes()
+        })
+    }
+}
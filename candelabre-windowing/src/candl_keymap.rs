@@ -0,0 +1,328 @@
+//! Declarative key bindings
+//!
+//! Instead of hardcoding keys in the match arms of the event loop, a
+//! `CandlKeymap` binds key chords and mouse buttons to messages, which are
+//! then sent to the state of a surface. A keymap can be built in code with
+//! `bind()`, or loaded from a simple config file, one binding per line:
+//!
+//! ```text
+//! # comments start with a '#'
+//! Escape:released = quit
+//! ctrl+S = save
+//! shift+Space:repeat = scroll
+//! MouseLeft = select
+//! ```
+//!
+//! A chord is made of optional modifiers (`shift`, `ctrl`, `alt`, `logo`),
+//! the name of a key (as named in `VirtualKeyCode`) or a mouse button
+//! (`MouseLeft`, `MouseRight`, `MouseMiddle`, `Mouse4`, ...), and optionally
+//! the trigger (`pressed` by default, `released` or `repeat`).
+
+use crate::CandlError;
+use glutin::event::{
+    ElementState, KeyboardInput, ModifiersState,
+    MouseButton, VirtualKeyCode, WindowEvent
+};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+/// Trigger of a binding
+///
+/// A key can trigger a binding when it's pressed, released, or when the key
+/// is kept pressed and the system repeat it. Mouse buttons don't repeat.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum CandlTrigger {
+    /// the key or the button is pressed
+    Pressed,
+    /// the key or the button is released
+    Released,
+    /// the key is kept pressed
+    Repeat
+}
+
+/// Input of a binding
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum CandlInput {
+    /// a key of the keyboard
+    Key(VirtualKeyCode),
+    /// a button of the mouse
+    Mouse(MouseButton)
+}
+
+/// Key chord
+///
+/// The combination of an input, the modifiers which must be held, and the
+/// trigger. Modifiers must match exactly, so `ctrl+S` isn't triggered by
+/// `ctrl+shift+S`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct CandlChord {
+    input: CandlInput,
+    modifiers: ModifiersState,
+    trigger: CandlTrigger
+}
+
+impl CandlChord {
+    /// chord for a key pressed, without modifiers
+    pub fn key(key: VirtualKeyCode) -> Self {
+        CandlChord {
+            input: CandlInput::Key(key),
+            modifiers: ModifiersState::empty(),
+            trigger: CandlTrigger::Pressed
+        }
+    }
+
+    /// chord for a mouse button pressed, without modifiers
+    pub fn mouse(button: MouseButton) -> Self {
+        CandlChord {
+            input: CandlInput::Mouse(button),
+            modifiers: ModifiersState::empty(),
+            trigger: CandlTrigger::Pressed
+        }
+    }
+
+    /// parse a chord, with the syntax of the keymap config file
+    pub fn parse(src: &str) -> Result<Self, CandlError> {
+        let (src, trigger) = match src.trim().rsplitn(2, ':').collect::<Vec<_>>()[..] {
+            [trigger, chord] => (chord, match trigger.trim().to_lowercase().as_str() {
+                "pressed" => CandlTrigger::Pressed,
+                "released" => CandlTrigger::Released,
+                "repeat" => CandlTrigger::Repeat,
+                _ => return Err(CandlError::KeymapError(
+                    format!("unknown trigger '{}'", trigger.trim())
+                ))
+            }),
+            _ => (src, CandlTrigger::Pressed)
+        };
+        let mut parts: Vec<&str> = src.split('+').map(|p| p.trim()).collect();
+        let name = parts.pop().unwrap_or("");
+        let mut modifiers = ModifiersState::empty();
+        for part in parts {
+            modifiers |= match part.to_lowercase().as_str() {
+                "shift" => ModifiersState::SHIFT,
+                "ctrl" => ModifiersState::CTRL,
+                "alt" => ModifiersState::ALT,
+                "logo" => ModifiersState::LOGO,
+                _ => return Err(CandlError::KeymapError(
+                    format!("unknown modifier '{}'", part)
+                ))
+            };
+        }
+        let input = match parse_input(name) {
+            Some(input) => input,
+            None => return Err(CandlError::KeymapError(
+                format!("unknown key or button '{}'", name)
+            ))
+        };
+        Ok(CandlChord { input, modifiers, trigger })
+    }
+
+    /// get the input of the chord
+    pub fn input(&self) -> CandlInput { self.input }
+
+    /// get the modifiers of the chord
+    pub fn modifiers(&self) -> ModifiersState { self.modifiers }
+
+    /// set the modifiers to hold
+    pub fn set_modifiers(self, modifiers: ModifiersState) -> Self {
+        Self { modifiers, ..self }
+    }
+
+    /// get the trigger of the chord
+    pub fn trigger(&self) -> CandlTrigger { self.trigger }
+
+    /// set the trigger of the chord (pressed by default)
+    pub fn set_trigger(self, trigger: CandlTrigger) -> Self {
+        Self { trigger, ..self }
+    }
+}
+
+/// Keymap
+///
+/// The keymap store the bindings between chords and messages. It tracks the
+/// modifiers and the keys held from the window events, so it needs to see all
+/// the events of the window it's used with, through `handle_event()`.
+#[derive(Debug)]
+pub struct CandlKeymap<M> {
+    bindings: Vec<(CandlChord, M)>,
+    modifiers: ModifiersState,
+    pressed: HashSet<VirtualKeyCode>
+}
+
+impl<M> Default for CandlKeymap<M> {
+    fn default() -> Self {
+        CandlKeymap {
+            bindings: vec!(),
+            modifiers: ModifiersState::empty(),
+            pressed: HashSet::default()
+        }
+    }
+}
+
+impl<M> CandlKeymap<M> {
+    /// create an empty keymap
+    pub fn new() -> Self { Self::default() }
+
+    /// load a keymap from a config string
+    ///
+    /// The `parse` function convert the name of a message, on the right side of
+    /// each binding, into a message.
+    pub fn from_config<F>(src: &str, mut parse: F) -> Result<Self, CandlError>
+    where F: FnMut(&str) -> Option<M> {
+        let mut keymap = Self::new();
+        for (idx, line) in src.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') { continue; }
+            let line_err = |e: CandlError| CandlError::KeymapError(
+                format!("line {}: {}", idx+1, e)
+            );
+            let (chord, message) = match line.splitn(2, '=').collect::<Vec<_>>()[..] {
+                [chord, message] => (chord, message.trim()),
+                _ => return Err(CandlError::KeymapError(
+                    format!("line {}: missing '=' in binding", idx+1)
+                ))
+            };
+            let chord = CandlChord::parse(chord).map_err(line_err)?;
+            let message = match parse(message) {
+                Some(message) => message,
+                None => return Err(CandlError::KeymapError(
+                    format!("line {}: unknown message '{}'", idx+1, message)
+                ))
+            };
+            keymap.bind(chord, message).map_err(line_err)?;
+        }
+        Ok(keymap)
+    }
+
+    /// load a keymap from a config file
+    pub fn from_file<P, F>(path: P, parse: F) -> Result<Self, CandlError>
+    where P: AsRef<Path>, F: FnMut(&str) -> Option<M> {
+        match fs::read_to_string(path.as_ref()) {
+            Ok(src) => Self::from_config(&src, parse),
+            Err(e) => Err(CandlError::KeymapError(
+                format!("unable to read '{}': {}", path.as_ref().display(), e)
+            ))
+        }
+    }
+
+    /// bind a chord to a message
+    ///
+    /// A chord can only be bound once, so an error is returned if the chord is
+    /// already in use. A mouse button can't be bound with the repeat trigger.
+    pub fn bind(&mut self, chord: CandlChord, message: M) -> Result<(), CandlError> {
+        if let (CandlInput::Mouse(_), CandlTrigger::Repeat) = (chord.input, chord.trigger) {
+            Err(CandlError::KeymapError(
+                String::from("mouse buttons can't use the repeat trigger")
+            ))
+        } else if self.get(&chord).is_some() {
+            Err(CandlError::KeymapError(
+                format!("conflicting binding for {:?}", chord)
+            ))
+        } else {
+            self.bindings.push((chord, message));
+            Ok(())
+        }
+    }
+
+    /// remove a binding, and get back its message
+    pub fn unbind(&mut self, chord: &CandlChord) -> Option<M> {
+        let idx = self.bindings.iter().position(|(c, _)| c == chord)?;
+        Some(self.bindings.remove(idx).1)
+    }
+
+    /// get the message bound to a chord
+    pub fn get(&self, chord: &CandlChord) -> Option<&M> {
+        self.bindings.iter().find(|(c, _)| c == chord).map(|(_, m)| m)
+    }
+
+    /// list all the chords of the keymap
+    pub fn chords(&self) -> Vec<CandlChord> {
+        self.bindings.iter().map(|(c, _)| *c).collect()
+    }
+
+    /// check if the keymap has no binding
+    pub fn is_empty(&self) -> bool { self.bindings.is_empty() }
+
+    /// get the message matching a window event, if any
+    pub fn handle_event(&mut self, event: &WindowEvent) -> Option<&M> {
+        let (input, trigger) = match event {
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.modifiers = *modifiers;
+                return None;
+            }
+            WindowEvent::Focused(false) => {
+                self.pressed.clear();
+                return None;
+            }
+            WindowEvent::KeyboardInput {
+                input: KeyboardInput {
+                    state,
+                    virtual_keycode: Some(key),
+                    ..
+                }, ..
+            } => (CandlInput::Key(*key), match state {
+                ElementState::Pressed =>
+                    if self.pressed.insert(*key) { CandlTrigger::Pressed }
+                    else { CandlTrigger::Repeat },
+                ElementState::Released => {
+                    self.pressed.remove(key);
+                    CandlTrigger::Released
+                }
+            }),
+            WindowEvent::MouseInput { state, button, .. } =>
+                (CandlInput::Mouse(*button), match state {
+                    ElementState::Pressed => CandlTrigger::Pressed,
+                    ElementState::Released => CandlTrigger::Released
+                }),
+            _ => return None
+        };
+        let chord = CandlChord { input, modifiers: self.modifiers, trigger };
+        self.get(&chord)
+    }
+}
+
+/// parse the name of a key or a mouse button
+fn parse_input(name: &str) -> Option<CandlInput> {
+    match name.to_lowercase().as_str() {
+        "mouseleft" => return Some(CandlInput::Mouse(MouseButton::Left)),
+        "mouseright" => return Some(CandlInput::Mouse(MouseButton::Right)),
+        "mousemiddle" => return Some(CandlInput::Mouse(MouseButton::Middle)),
+        lname if lname.starts_with("mouse") => {
+            if let Ok(nb) = lname[5..].parse() {
+                return Some(CandlInput::Mouse(MouseButton::Other(nb)));
+            }
+        }
+        _ => ()
+    }
+    KEY_NAMES.iter()
+        .find(|(kname, _)| kname.eq_ignore_ascii_case(name))
+        .map(|(_, key)| CandlInput::Key(*key))
+}
+
+macro_rules! key_names {
+    ($($key:ident),*) => {
+        /// names of all the keys, as written in the keymap config
+        const KEY_NAMES: &[(&str, VirtualKeyCode)] = &[
+            $((stringify!($key), VirtualKeyCode::$key)),*
+        ];
+    }
+}
+
+key_names!(
+    Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9, Key0,
+    A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+    Escape, F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12, F13, F14, F15,
+    F16, F17, F18, F19, F20, F21, F22, F23, F24, Snapshot, Scroll, Pause,
+    Insert, Home, Delete, End, PageDown, PageUp, Left, Up, Right, Down, Back,
+    Return, Space, Compose, Caret, Numlock, Numpad0, Numpad1, Numpad2, Numpad3,
+    Numpad4, Numpad5, Numpad6, Numpad7, Numpad8, Numpad9, AbntC1, AbntC2, Add,
+    Apostrophe, Apps, At, Ax, Backslash, Calculator, Capital, Colon, Comma,
+    Convert, Decimal, Divide, Equals, Grave, Kana, Kanji, LAlt, LBracket,
+    LControl, LShift, LWin, Mail, MediaSelect, MediaStop, Minus, Multiply, Mute,
+    MyComputer, NavigateForward, NavigateBackward, NextTrack, NoConvert,
+    NumpadComma, NumpadEnter, NumpadEquals, OEM102, Period, PlayPause, Power,
+    PrevTrack, RAlt, RBracket, RControl, RShift, RWin, Semicolon, Slash, Sleep,
+    Stop, Subtract, Sysrq, Tab, Underline, Unlabeled, VolumeDown, VolumeUp,
+    Wake, WebBack, WebFavorites, WebForward, WebHome, WebRefresh, WebSearch,
+    WebStop, Yen, Copy, Paste, Cut
+);
@@ -0,0 +1,138 @@
+//! Monitors and video modes
+//!
+//! Picking the first video mode of a monitor gives an arbitrary mode, so this
+//! module provides a way to query the monitors and to select a video mode with
+//! some criteria.
+
+use glutin::dpi::{PhysicalPosition, PhysicalSize};
+use glutin::event_loop::EventLoopWindowTarget;
+use glutin::monitor::{MonitorHandle, VideoMode};
+use std::cmp::{Ordering, Reverse};
+
+/// Video mode criteria
+///
+/// The criteria used to select a video mode, from the most important to the
+/// least one: the resolution, the refresh rate, and the bit depth. Without
+/// resolution, the highest one is selected, which is the native resolution of
+/// the monitor most of the time.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct CandlModeCriteria {
+    size: Option<(u32, u32)>,
+    highest_refresh: bool,
+    bit_depth: Option<u16>
+}
+
+impl Default for CandlModeCriteria {
+    /// Default:
+    ///
+    /// Highest resolution, highest refresh rate, and highest bit depth
+    fn default() -> Self {
+        CandlModeCriteria {
+            size: None,
+            highest_refresh: true,
+            bit_depth: None
+        }
+    }
+}
+
+impl CandlModeCriteria {
+    /// get the wanted resolution, if any
+    pub fn size(&self) -> Option<(u32, u32)> { self.size }
+
+    /// set the wanted resolution, the closest one will be selected
+    pub fn set_size<S: Into<Option<(u32, u32)>>>(self, size: S) -> Self {
+        Self { size: size.into(), ..self }
+    }
+
+    /// get if the highest refresh rate is preferred
+    pub fn highest_refresh(&self) -> bool { self.highest_refresh }
+
+    /// set if the highest refresh rate is preferred, or the lowest one
+    pub fn set_highest_refresh(self, highest_refresh: bool) -> Self {
+        Self { highest_refresh, ..self }
+    }
+
+    /// get the wanted bit depth, if any
+    pub fn bit_depth(&self) -> Option<u16> { self.bit_depth }
+
+    /// set the wanted bit depth, the closest one will be selected
+    pub fn set_bit_depth<B: Into<Option<u16>>>(self, bit_depth: B) -> Self {
+        Self { bit_depth: bit_depth.into(), ..self }
+    }
+
+    /// compare two video modes, the greater is the better match
+    pub fn compare(&self, a: &VideoMode, b: &VideoMode) -> Ordering {
+        self.rank(a).cmp(&self.rank(b))
+    }
+
+    /// sort key of a video mode, the greater is the better match
+    fn rank(&self, mode: &VideoMode) -> (Reverse<u64>, u64, i32, Reverse<u16>, u16) {
+        let size = mode.size();
+        let (size_dist, area) = match self.size {
+            Some((w, h)) => (
+                (size.width as i64 - w as i64).unsigned_abs()
+                    + (size.height as i64 - h as i64).unsigned_abs(),
+                0
+            ),
+            None => (0, size.width as u64 * size.height as u64)
+        };
+        let refresh = if self.highest_refresh { mode.refresh_rate() as i32 }
+            else { -(mode.refresh_rate() as i32) };
+        let depth_dist = match self.bit_depth {
+            Some(depth) => mode.bit_depth().max(depth) - mode.bit_depth().min(depth),
+            None => 0
+        };
+        (Reverse(size_dist), area, refresh, Reverse(depth_dist), mode.bit_depth())
+    }
+}
+
+/// Monitor
+///
+/// A light wrapper around the glutin monitor handle, to query the
+/// monitor and select one of its video modes.
+#[derive(Clone, Debug)]
+pub struct CandlMonitor {
+    handle: MonitorHandle
+}
+
+impl From<MonitorHandle> for CandlMonitor {
+    fn from(handle: MonitorHandle) -> Self { CandlMonitor { handle } }
+}
+
+impl CandlMonitor {
+    /// get the primary monitor
+    pub fn primary<T>(el: &EventLoopWindowTarget<T>) -> Self {
+        CandlMonitor::from(el.primary_monitor())
+    }
+
+    /// list all the available monitors
+    pub fn all<T>(el: &EventLoopWindowTarget<T>) -> Vec<Self> {
+        el.available_monitors().map(CandlMonitor::from).collect()
+    }
+
+    /// get the glutin handle of the monitor
+    pub fn handle(&self) -> &MonitorHandle { &self.handle }
+
+    /// get the name of the monitor, if available
+    pub fn name(&self) -> Option<String> { self.handle.name() }
+
+    /// get the position of the top-left corner of the monitor, in physical
+    /// pixels, on the desktop
+    pub fn position(&self) -> PhysicalPosition<i32> { self.handle.position() }
+
+    /// get the resolution of the monitor, in physical pixels
+    pub fn size(&self) -> PhysicalSize<u32> { self.handle.size() }
+
+    /// get the scale factor of the monitor
+    pub fn scale_factor(&self) -> f64 { self.handle.scale_factor() }
+
+    /// list all the video modes of the monitor
+    pub fn video_modes(&self) -> Vec<VideoMode> { self.handle.video_modes().collect() }
+
+    /// select the video mode matching the best the criteria
+    ///
+    /// Return `None` only if the monitor doesn't have any video mode.
+    pub fn select_mode(&self, criteria: &CandlModeCriteria) -> Option<VideoMode> {
+        self.handle.video_modes().max_by(|a, b| criteria.compare(a, b))
+    }
+}
@@ -20,8 +20,13 @@
 //!
 //! * `CandlSurface`, a window type who generate a surface for using OpenGL
 //! * `CandlManager`, a window manager to enable using multiple windows in a
-//! single application / thread
+//!   single application / thread
 //! * `CandlWindow`, a trait on which `CandlSurface` is based on
+//! * `CandlKeymap`, a set of bindings between the inputs and the messages of
+//!   a surface
+//! * `CandlMonitor`, a way to query the monitors and select their video modes
+//! * `CandlGlDebug`, the OpenGL debug output of a surface
+//! * `CandlGl`, the OpenGL functions loaded for the context of a surface
 //!
 //! ## `CandlSurface`
 //! 
@@ -47,6 +52,13 @@
 //! need a simple way to handle a window, maybe the `CandlWindow` is the right
 //! tool to do it.
 //! 
+//! ## `CandlKeymap`
+//! 
+//! Matching keys in the event loop quickly become a long list of match arms.
+//! The keymap turn this code into data: each surface can hold a keymap, which
+//! convert key chords and mouse buttons into messages for the state. Keymaps
+//! can be built in code or loaded from a config file.
+//! 
 //! ## About state in `CandlSurface` and `CandlManager`
 //! 
 //! It's possible to add state into the `CandlSurface` and the `CandlManager`.
@@ -55,25 +67,43 @@
 //! state of the surface. In the examples, it works a little bit like a FRP,
 //! with the state acting like a store to handle a model, pass to the renderer.
 //! Take a look.
+//! 
+//! The state also answers the close requests of its surface: it can confirm
+//! them, veto them, or defer them, e.g. to ask the user about unsaved changes.
 
 #![deny(missing_docs)]
 
-use gl;
 pub use glutin;
 use glutin::{
     Api, ContextBuilder, GlProfile, GlRequest, NotCurrent,
-    PossiblyCurrent, WindowedContext
+    PixelFormat, PossiblyCurrent, WindowedContext
 };
 use glutin::{ContextError, CreationError};
-use glutin::dpi::{LogicalSize, PhysicalSize};
+use glutin::dpi::{LogicalSize, PhysicalPosition, PhysicalSize};
+use glutin::event::WindowEvent;
 use glutin::event_loop::EventLoopWindowTarget;
 use glutin::monitor::VideoMode;
 use glutin::window::{Fullscreen, WindowBuilder, Window, WindowId};
-use std::collections::HashMap;
+use std::any::Any;
+use std::borrow::Borrow;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fmt;
+use std::hash::Hash;
 use std::marker::PhantomData;
-use std::os::raw::c_void;
+use std::path::PathBuf;
 
+pub use self::candl_debug::{CandlGlDebug, CandlGlMessage, CandlGlSeverity};
+pub use self::candl_gl::CandlGl;
+use self::candl_debug::CandlGlDebugHandler;
+pub use self::candl_keymap::{
+    CandlChord, CandlInput, CandlKeymap, CandlTrigger
+};
+pub use self::candl_monitor::{CandlModeCriteria, CandlMonitor};
+
+mod candl_debug;
+pub mod candl_gl;
+mod candl_keymap;
+mod candl_monitor;
 
 // =======================================================================
 // =======================================================================
@@ -85,12 +115,22 @@ use std::os::raw::c_void;
 /// 
 /// This trait must be used by any structure which want to fill the gap between
 /// a `CandlWindow` and OpenGL.
+/// 
+/// The lifecycle of a renderer is the following: `init()` creates it without
+/// any OpenGL context, `setup()` gives it the live context of its surface, so
+/// it can create its OpenGL resources, then the surface calls the other
+/// methods until `teardown()`, just before the destruction of the context.
+/// Each method receiving a `CandlContext` is called with this context as the
+/// current one.
 pub trait CandlRenderer<R, S: CandlUpdate<M>, M> {
-    /// init the renderer
+    /// init the renderer, before the creation of the OpenGL context
     fn init() -> R;
 
-    /// call from `CandlSurface` after the gl initialization
-    fn finalize(&mut self);
+    /// set up the renderer with the live OpenGL context of the surface
+    ///
+    /// Called once, before any other method using the context. If it fails,
+    /// the creation of the surface fails with the same error.
+    fn setup(&mut self, ctx: &CandlContext) -> Result<(), CandlError>;
 
     /// set the scale factor when it changed
     fn set_scale_factor(&mut self, scale_factor: f64);
@@ -100,6 +140,26 @@ pub trait CandlRenderer<R, S: CandlUpdate<M>, M> {
 
     /// call for redraw the current OpenGL context
     fn draw_frame(&mut self, state: &S);
+
+    /// the window of the surface gained or lost the focus
+    fn focus_changed(&mut self, _focused: bool) {}
+
+    /// the window of the surface was minimized, or restored
+    ///
+    /// A surface with a null size is considered hidden, it doesn't need to be
+    /// drawn, so it's a good time to pause the animations.
+    fn visibility_changed(&mut self, _visible: bool) {}
+
+    /// release the OpenGL resources, before the destruction of the context
+    fn teardown(&mut self, _ctx: &CandlContext) {}
+
+    /// set up the renderer again with a new OpenGL context
+    ///
+    /// All the OpenGL resources created with the previous context are gone,
+    /// and must be created again. By default, `setup()` is called again.
+    fn context_recreated(&mut self, ctx: &CandlContext) -> Result<(), CandlError> {
+        self.setup(ctx)
+    }
 }
 
 /// Update Trait
@@ -111,6 +171,23 @@ pub trait CandlRenderer<R, S: CandlUpdate<M>, M> {
 pub trait CandlUpdate<M> {
     /// the state handler of
     fn update(&mut self, message: M);
+
+    /// update the state, and tell if the change affects the rendering
+    ///
+    /// The surface calls this method, and asks for a redraw when it returns
+    /// `true`. By default, `update()` is called and every message affects the
+    /// rendering, override it to avoid useless redraws.
+    fn update_redraw(&mut self, message: M) -> bool {
+        self.update(message);
+        true
+    }
+
+    /// answer a request to close the surface
+    ///
+    /// Called by `request_close()` on the surface or the manager, it lets the
+    /// state keep the window open, e.g. when there are unsaved changes. By
+    /// default, the close is confirmed.
+    fn close_requested(&mut self) -> CandlCloseResponse { CandlCloseResponse::Confirm }
 }
 
 // =======================================================================
@@ -131,7 +208,11 @@ pub enum CandlError {
     /// OpenGL context usage error
     ContextError(ContextError),
     /// Candelabre internal error
-    InternalError(&'static str)
+    InternalError(&'static str),
+    /// Keymap definition or loading error
+    KeymapError(String),
+    /// Renderer setup error
+    RendererError(String)
 }
 
 impl fmt::Display for CandlError {
@@ -142,7 +223,11 @@ impl fmt::Display for CandlError {
             CandlError::ContextError(ref e) =>
                 write!(f, "OpenGL context usage error: {}", e),
             CandlError::InternalError(e) =>
-                write!(f, "Candelabre internal error: {}", e)
+                write!(f, "Candelabre internal error: {}", e),
+            CandlError::KeymapError(ref e) =>
+                write!(f, "Candelabre keymap error: {}", e),
+            CandlError::RendererError(ref e) =>
+                write!(f, "Candelabre renderer error: {}", e)
         }
     }
 }
@@ -161,13 +246,17 @@ impl From<ContextError> for CandlError {
 /// [luminance-windowing](https://docs.rs/luminance-windowing/0.8.1/luminance_windowing/)
 /// to avoid the call of this crate and separate a little bit more luminance from
 /// candelabre. The idea is to maybe be able to use candelabre without luminance.
+/// 
+/// The dimensions are logical sizes, so the window keeps the same apparent
+/// size whatever the scale factor of the monitor. The physical size, the one
+/// the renderer receives, is the logical size multiplied by the scale factor.
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum CandlDimension {
-    /// cassic windowed mode
+    /// cassic windowed mode (logical size)
     Classic(u32, u32),
     /// fullscreen (for only one monitor)
     Fullscreen,
-    /// fullscreen mode but with specific dimensions
+    /// fullscreen mode but with specific dimensions (logical size)
     FullscreenSpecific(u32, u32)
 }
 
@@ -188,14 +277,24 @@ pub enum CursorMode {
 ///
 /// This type is an extract from
 /// [luminance-windowing](https://docs.rs/luminance-windowing/0.8.1/luminance_windowing/)
+/// 
+/// The pixel format options are minimal requirements, the pixel format really
+/// obtained can be checked with `CandlWindow::pixel_format()`.
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub struct CandlOptions {
+    alpha_bits: u8,
+    color_bits: u8,
     cursor_mode: CursorMode,
+    debug: bool,
     decorations: bool,
+    depth_bits: u8,
+    hardware_acceleration: Option<bool>,
     max_size: Option<(u32, u32)>,
     min_size: Option<(u32, u32)>,
     on_top: bool,
     samples: Option<u32>,
+    srgb: Option<bool>,
+    stencil_bits: u8,
     transparent: bool,
     vsync: bool
 }
@@ -203,15 +302,25 @@ pub struct CandlOptions {
 impl Default for CandlOptions {
     /// Default:
     /// 
-    /// Default options for a window, with cursor visible
+    /// Default options for a window, with cursor visible, and a pixel format
+    /// with 24 bits for color, 8 bits for alpha, 24 bits for depth and 8 bits
+    /// for stencil, without preference for the hardware acceleration, and the
+    /// glutin default for sRGB
     fn default() -> Self {
         CandlOptions {
+            alpha_bits: 8,
+            color_bits: 24,
             cursor_mode: CursorMode::Visible,
+            debug: false,
             decorations: true,
+            depth_bits: 24,
+            hardware_acceleration: None,
             max_size: None,
             min_size: None,
             on_top: false,
             samples: None,
+            srgb: None,
+            stencil_bits: 8,
             transparent: false,
             vsync: false
         }
@@ -219,6 +328,23 @@ impl Default for CandlOptions {
 }
 
 impl CandlOptions {
+    /// get the minimal number of bits for the alpha channel
+    pub fn alpha_bits(&self) -> u8 { self.alpha_bits }
+
+    /// set the minimal number of bits for the alpha channel (8 by default)
+    pub fn set_alpha_bits(self, alpha_bits: u8) -> Self {
+        Self { alpha_bits, ..self }
+    }
+
+    /// get the minimal number of bits for the color, without alpha
+    pub fn color_bits(&self) -> u8 { self.color_bits }
+
+    /// set the minimal number of bits for the color, without alpha (24 by
+    /// default)
+    pub fn set_color_bits(self, color_bits: u8) -> Self {
+        Self { color_bits, ..self }
+    }
+
     /// get the cursor current visiblity
     pub fn cursor_mode(&self) -> CursorMode { self.cursor_mode }
 
@@ -227,6 +353,18 @@ impl CandlOptions {
         Self { cursor_mode, ..self }
     }
 
+    /// get if the OpenGL debug output is enabled
+    pub fn debug(&self) -> bool { self.debug }
+
+    /// enable the OpenGL debug output (false by default)
+    ///
+    /// The context is created with the debug flag, and the messages of the
+    /// driver are routed to the `log` facade, or to the handler set on the
+    /// surface.
+    pub fn set_debug(self, debug: bool) -> Self {
+        Self { debug, ..self }
+    }
+
     /// get the window current decoration status (default true)
     pub fn decorations(&self) -> bool { self.decorations }
 
@@ -235,6 +373,23 @@ impl CandlOptions {
         Self { decorations, ..self }
     }
 
+    /// get the minimal number of bits for the depth buffer
+    pub fn depth_bits(&self) -> u8 { self.depth_bits }
+
+    /// set the minimal number of bits for the depth buffer (24 by default)
+    pub fn set_depth_bits(self, depth_bits: u8) -> Self {
+        Self { depth_bits, ..self }
+    }
+
+    /// get the hardware acceleration preference
+    pub fn hardware_acceleration(&self) -> Option<bool> { self.hardware_acceleration }
+
+    /// set the hardware acceleration preference: `Some(true)` for hardware
+    /// only, `Some(false)` for software only, and `None` for any (default)
+    pub fn set_hardware_acceleration<H: Into<Option<bool>>>(self, hardware_acceleration: H) -> Self {
+        Self { hardware_acceleration: hardware_acceleration.into(), ..self }
+    }
+
     /// get the maximal size, if set, or none otherwise
     pub fn max_size(&self) -> Option<(u32, u32)> { self.max_size }
 
@@ -267,6 +422,24 @@ impl CandlOptions {
         Self { samples: samples.into(), ..self }
     }
 
+    /// get if a sRGB capable pixel format is required, `None` if it's left
+    /// to glutin
+    pub fn srgb(&self) -> Option<bool> { self.srgb }
+
+    /// set if a sRGB capable pixel format is required (left to glutin by
+    /// default)
+    pub fn set_srgb<S: Into<Option<bool>>>(self, srgb: S) -> Self {
+        Self { srgb: srgb.into(), ..self }
+    }
+
+    /// get the minimal number of bits for the stencil buffer
+    pub fn stencil_bits(&self) -> u8 { self.stencil_bits }
+
+    /// set the minimal number of bits for the stencil buffer (8 by default)
+    pub fn set_stencil_bits(self, stencil_bits: u8) -> Self {
+        Self { stencil_bits, ..self }
+    }
+
     /// get if the window can be transparent (default false)
     pub fn transparent(&self) -> bool { self.transparent }
 
@@ -301,6 +474,136 @@ pub enum CandlCurrentWrapper {
     NotCurrent(WindowedContext<NotCurrent>)
 }
 
+/// Close request answer
+///
+/// The answer of the state to a request to close its surface. A deferred
+/// close stays pending until the application calls `confirm_close()` or
+/// `cancel_close()`, e.g. after asking the user.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CandlCloseResponse {
+    /// the surface can be closed
+    Confirm,
+    /// the surface stays open
+    Veto,
+    /// the answer will come later
+    Defer
+}
+
+/// Live OpenGL context
+///
+/// The handle given to the renderer during its lifecycle, with what it needs
+/// to work with the OpenGL context of its surface. The context is the current
+/// one only during the call receiving the handle.
+#[derive(Clone, Debug)]
+pub struct CandlContext {
+    window_id: WindowId,
+    gl: CandlGl,
+    pixel_format: PixelFormat
+}
+
+impl CandlContext {
+    /// create the handle of a context, loading its OpenGL functions
+    pub fn new(ctx: &WindowedContext<PossiblyCurrent>) -> Self {
+        CandlContext {
+            window_id: ctx.window().id(),
+            gl: CandlGl::load(ctx),
+            pixel_format: ctx.get_pixel_format()
+        }
+    }
+
+    /// get the id of the window owning the context
+    pub fn window_id(&self) -> WindowId { self.window_id }
+
+    /// get the OpenGL functions of the context
+    pub fn gl(&self) -> &CandlGl { &self.gl }
+
+    /// get the pixel format obtained for the context
+    pub fn pixel_format(&self) -> &PixelFormat { &self.pixel_format }
+}
+
+/// File drop
+///
+/// When a file is dragged over a surface, then dropped or not, the surface
+/// generates a `CandlDrop`, with the last known position of the cursor over
+/// the surface. The cursor doesn't move during a drag, so it's the position
+/// before the drag, and it's unknown if the cursor wasn't over the surface,
+/// as told by the enter and leave events. Dragging several files at once
+/// generates one `CandlDrop` per file.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CandlDrop {
+    /// a file is hovering the surface
+    Hovered {
+        /// path of the file
+        path: PathBuf,
+        /// position of the cursor, in physical pixels
+        position: Option<PhysicalPosition<f64>>
+    },
+    /// a file was dropped on the surface
+    Dropped {
+        /// path of the file
+        path: PathBuf,
+        /// position of the cursor, in physical pixels
+        position: Option<PhysicalPosition<f64>>
+    },
+    /// the hovering files left the surface without being dropped
+    Cancelled
+}
+
+/// Text input
+///
+/// When the text input is enabled on a surface, the characters typed in the
+/// window are collected by the surface, and can then be read as a stream. An
+/// input method (IME) composes text in two steps: first the preedit, the text
+/// being composed, and then the commit, the final text.
+/// 
+/// NOTE: glutin only reports the composed characters, so the preedit and
+/// commit steps are never generated by the surface itself, but an input
+/// method integration can push them with `push_text_input()`. For the same
+/// reason, glutin can't turn the input method of the window on or off (winit
+/// only gained `set_ime_allowed()` in later versions), the IME state of the
+/// surface only filters the text input it collects.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CandlTextInput {
+    /// a character typed (control characters are left to the keymap)
+    Char(char),
+    /// text being composed, with the selected range in bytes inside the text
+    Preedit {
+        /// the composed text
+        text: String,
+        /// selection of the composition cursor, if any
+        cursor: Option<(usize, usize)>
+    },
+    /// composition finished, with the final text
+    Commit(String)
+}
+
+/// handler converting the file drops into messages
+struct CandlDropHandler<M>(Box<dyn FnMut(CandlDrop) -> Option<M>>);
+
+impl<M> fmt::Debug for CandlDropHandler<M> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "CandlDropHandler")
+    }
+}
+
+/// observer notified after each message given to the state
+struct CandlObserver<D>(Box<dyn FnMut(&D)>);
+
+impl<D> fmt::Debug for CandlObserver<D> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "CandlObserver")
+    }
+}
+
+/// middleware of a surface, between the surface and its state
+struct CandlMiddleware<M>(Box<dyn FnMut(M) -> Vec<M>>);
+
+impl<M> fmt::Debug for CandlMiddleware<M> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "CandlMiddleware")
+    }
+}
+
 /// No state
 /// 
 /// This object has only one goal: to handle the case we don't want to have a
@@ -309,6 +612,8 @@ pub struct CandlNoState {}
 
 impl CandlUpdate<()> for CandlNoState {
     fn update(&mut self, _: ()) {}
+
+    fn update_redraw(&mut self, _: ()) -> bool { false }
 }
 
 // =======================================================================
@@ -322,7 +627,14 @@ impl CandlUpdate<()> for CandlNoState {
 /// This trait can be used to create a new kind of `CandlSurface`, with a
 /// deeper connection with your code.
 pub trait CandlWindow {
+    /// type of the messages given to the state of the window, routed by the
+    /// middlewares of the `CandlManager`, `()` if there is none
+    type Message;
+
     /// code to init the basis of a window with an OpenGL context
+    /// 
+    /// The global functions of the `gl` crate aren't loaded, use
+    /// `CandlGl::load()` to get the functions of the new context.
     fn init<T>(
         el: &EventLoopWindowTarget<T>,
         video_mode: VideoMode,
@@ -354,19 +666,27 @@ pub trait CandlWindow {
                         Some(Fullscreen::Exclusive(video_mode))
                     )
         };
-        let ctx = ContextBuilder::new()
+        let builder = ContextBuilder::new()
             .with_gl(GlRequest::Specific(Api::OpenGl, (3, 3)))
             .with_gl_profile(GlProfile::Core)
+            .with_gl_debug_flag(options.debug())
             .with_vsync(options.vsync())
             .with_multisampling(options.samples().unwrap_or(0) as u16)
             .with_double_buffer(Some(true))
-            .build_windowed(win_builder, &el)?;
+            .with_pixel_format(options.color_bits(), options.alpha_bits())
+            .with_depth_buffer(options.depth_bits())
+            .with_stencil_buffer(options.stencil_bits())
+            .with_hardware_acceleration(options.hardware_acceleration());
+        let builder = match options.srgb() {
+            Some(srgb) => builder.with_srgb(srgb),
+            None => builder
+        };
+        let ctx = builder.build_windowed(win_builder, &el)?;
         let ctx = unsafe { ctx.make_current().map_err(|(_, e)| e)? };
         ctx.window().set_cursor_visible(match options.cursor_mode() {
             CursorMode::Visible => true,
             CursorMode::Invisible => false
         });
-        gl::load_with(|s| ctx.get_proc_address(s) as *const c_void);
         Ok(ctx)
     }
 
@@ -379,9 +699,28 @@ pub trait CandlWindow {
     /// change the OpenGL context
     fn set_ctx(&mut self, nctx: CandlCurrentWrapper);
 
+    /// get the pixel format obtained for the OpenGL context, the context must
+    /// be the current one
+    fn pixel_format(&self) -> Result<PixelFormat, CandlError> {
+        match self.ctx_ref() {
+            CandlCurrentWrapper::PossiblyCurrent(ctx) => Ok(ctx.get_pixel_format()),
+            CandlCurrentWrapper::NotCurrent(_) =>
+                Err(CandlError::InternalError("The context of this surface is not the current context"))
+        }
+    }
+
     /// handle resize event
     fn resize(&mut self, nsize: PhysicalSize<u32>);
 
+    /// handle scale factor change event
+    ///
+    /// The scale factor changes when the window moves to a monitor with a
+    /// different DPI, and the window gets a new physical size at the same
+    /// time. By default, only the new size is handled.
+    fn rescale(&mut self, _scale_factor: f64, nsize: PhysicalSize<u32>) {
+        self.resize(nsize);
+    }
+
     /// swap the buffer
     fn swap_buffers(&mut self);
 }
@@ -403,6 +742,12 @@ where R: CandlRenderer<R, D, M>, D: CandlUpdate<M> {
     render: Option<R>,
     state: Option<D>,
     video_mode: Option<VideoMode>,
+    mode_criteria: Option<CandlModeCriteria>,
+    keymap: CandlKeymap<M>,
+    drop_handler: Option<CandlDropHandler<M>>,
+    gl_debug_handler: Option<CandlGlDebugHandler>,
+    observers: Vec<CandlObserver<D>>,
+    middlewares: Vec<CandlMiddleware<M>>,
     message: PhantomData<M>
 }
 
@@ -428,6 +773,12 @@ where R: CandlRenderer<R, D, M>, D: CandlUpdate<M> {
             render: None,
             state: None,
             video_mode: None,
+            mode_criteria: None,
+            keymap: CandlKeymap::default(),
+            drop_handler: None,
+            gl_debug_handler: None,
+            observers: Vec::new(),
+            middlewares: Vec::new(),
             message: PhantomData
         }
     }
@@ -452,13 +803,70 @@ where R: CandlRenderer<R, D, M>, D: CandlUpdate<M> {
     }
 
     /// set the video mode for the window (from the monitor handle)
+    ///
+    /// Without video mode, the builder select one on the primary monitor,
+    /// with the criteria given by `mode_criteria()`.
     pub fn video_mode(self, video_mode: VideoMode) -> Self {
         Self {video_mode: Some(video_mode), ..self}
     }
 
+    /// set the criteria to select the video mode, if no video mode is given
+    ///
+    /// By default, the highest resolution is selected, or the resolution the
+    /// closest to the dimension for `CandlDimension::FullscreenSpecific`.
+    pub fn mode_criteria(self, criteria: CandlModeCriteria) -> Self {
+        Self {mode_criteria: Some(criteria), ..self}
+    }
+
+    /// set the keymap of the surface (empty by default)
+    pub fn keymap(self, keymap: CandlKeymap<M>) -> Self {
+        Self {keymap, ..self}
+    }
+
+    /// set the function converting the file drops into messages
+    pub fn on_drop<F>(self, handler: F) -> Self
+    where F: FnMut(CandlDrop) -> Option<M> + 'static {
+        Self {drop_handler: Some(CandlDropHandler(Box::new(handler))), ..self}
+    }
+
+    /// set the handler receiving the OpenGL debug messages, when the debug
+    /// option is enabled (the `log` facade is used otherwise)
+    pub fn on_gl_debug<F>(self, handler: F) -> Self
+    where F: FnMut(&CandlGlMessage) + 'static {
+        Self {gl_debug_handler: Some(Box::new(handler)), ..self}
+    }
+
+    /// add an observer of the state, notified after each message
+    pub fn observe<F>(mut self, observer: F) -> Self
+    where F: FnMut(&D) + 'static {
+        self.observers.push(CandlObserver(Box::new(observer)));
+        self
+    }
+
+    /// add a middleware, called with each message before the state
+    ///
+    /// The middleware returns the messages to give to the next middleware,
+    /// or to the state for the last one: none to filter the message, another
+    /// one to transform it, or several ones to fan it out. The middlewares
+    /// are called in the order they are added.
+    pub fn middleware<F>(mut self, middleware: F) -> Self
+    where F: FnMut(M) -> Vec<M> + 'static {
+        self.middlewares.push(CandlMiddleware(Box::new(middleware)));
+        self
+    }
+
     /// try to build the surface
     pub fn build<T>(self, el: &EventLoopWindowTarget<T>) -> Result<CandlSurface<R, D, M>, CandlError> {
-        match (self.render, self.state, self.video_mode) {
+        let (dim, mode_criteria) = (self.dim, self.mode_criteria);
+        let video_mode = self.video_mode.or_else(|| {
+            let criteria = mode_criteria.unwrap_or_else(|| match dim {
+                CandlDimension::FullscreenSpecific(w, h) =>
+                    CandlModeCriteria::default().set_size((w, h)),
+                _ => CandlModeCriteria::default()
+            });
+            CandlMonitor::primary(el).select_mode(&criteria)
+        });
+        match (self.render, self.state, video_mode) {
             (None, None, _) =>
                 Err(CandlError::InternalError("You must specify the renderer and the state!")),
             (None, Some(_), _) =>
@@ -466,9 +874,9 @@ where R: CandlRenderer<R, D, M>, D: CandlUpdate<M> {
             (Some(_), None, _) =>
                 Err(CandlError::InternalError("You must specify the state! (use 'nostate'?)")),
             (_, _, None) =>
-                Err(CandlError::InternalError("Please specify the video mode for the window")),
-            (Some(render), Some(state), Some(video_mode)) =>
-                CandlSurface::window_builder(
+                Err(CandlError::InternalError("No video mode available on the primary monitor")),
+            (Some(render), Some(state), Some(video_mode)) => {
+                let mut surface = CandlSurface::window_builder(
                     el,
                     video_mode,
                     self.dim,
@@ -476,7 +884,16 @@ where R: CandlRenderer<R, D, M>, D: CandlUpdate<M> {
                     self.options,
                     render,
                     state
-                )
+                )?;
+                surface.keymap = self.keymap;
+                surface.drop_handler = self.drop_handler;
+                surface.observers = self.observers;
+                surface.middlewares = self.middlewares;
+                if let (Some(debug), Some(handler)) = (&mut surface.gl_debug, self.gl_debug_handler) {
+                    debug.set_handler(handler);
+                }
+                Ok(surface)
+            }
         }
     }
 }
@@ -506,12 +923,54 @@ where R: CandlRenderer<R, D, M>, D: CandlUpdate<M> {
     ctx: Option<CandlCurrentWrapper>,
     render: R,
     state: D,
+    context: CandlContext,
+    gl_debug: Option<CandlGlDebug>,
+    keymap: CandlKeymap<M>,
+    drop_handler: Option<CandlDropHandler<M>>,
+    observers: Vec<CandlObserver<D>>,
+    middlewares: Vec<CandlMiddleware<M>>,
+    cursor: Option<PhysicalPosition<f64>>,
+    text_input: bool,
+    ime: bool,
+    ime_area: Option<(PhysicalPosition<i32>, PhysicalSize<u32>)>,
+    text_queue: VecDeque<CandlTextInput>,
+    scale_factor: f64,
+    size: PhysicalSize<u32>,
+    resize_pending: bool,
+    visible: bool,
+    hidden: bool,
+    minimized: bool,
+    maximized: bool,
+    focused: bool,
+    hovered: bool,
+    close_pending: bool,
+    closing: bool,
+    video_mode: VideoMode,
+    dim: CandlDimension,
+    title: String,
+    options: CandlOptions,
     message: PhantomData<M>,
     redraw: bool
 }
 
+impl<R, D, M> Drop for CandlSurface<R, D, M>
+where R: CandlRenderer<R, D, M>, D: CandlUpdate<M> {
+    /// tear down the renderer before the destruction of the context
+    ///
+    /// The renderer can only release its resources if the context is the
+    /// current one, otherwise its teardown is skipped. The `CandlManager`
+    /// makes the context current before removing or dropping a surface.
+    fn drop(&mut self) {
+        if let Some(CandlCurrentWrapper::PossiblyCurrent(_)) = &self.ctx {
+            self.render.teardown(&self.context);
+        }
+    }
+}
+
 impl<R, D, M> CandlWindow for CandlSurface<R, D, M>
 where R: CandlRenderer<R, D, M>, D: CandlUpdate<M> {
+    type Message = M;
+
     /// get the OpenGL context from the surface
     fn ctx(&mut self) -> CandlCurrentWrapper { self.ctx.take().unwrap() }
 
@@ -519,7 +978,16 @@ where R: CandlRenderer<R, D, M>, D: CandlUpdate<M> {
     fn ctx_ref(&self) -> &CandlCurrentWrapper { self.ctx.as_ref().unwrap() }
 
     /// change the OpenGL context (make current or not current)
-    fn set_ctx(&mut self, nctx: CandlCurrentWrapper) { self.ctx = Some(nctx); }
+    fn set_ctx(&mut self, nctx: CandlCurrentWrapper) {
+        // the window was resized while another context was current
+        if let CandlCurrentWrapper::PossiblyCurrent(ctx) = &nctx {
+            if self.resize_pending {
+                ctx.resize(self.size);
+                self.resize_pending = false;
+            }
+        }
+        self.ctx = Some(nctx);
+    }
 
     /// swap the OpenGL back buffer and current buffer
     fn swap_buffers(&mut self) {
@@ -528,13 +996,25 @@ where R: CandlRenderer<R, D, M>, D: CandlUpdate<M> {
         }
     }
 
-    /// handle resize event
+    /// handle resize event, a null size means the window is minimized
     fn resize(&mut self, nsize: PhysicalSize<u32>) {
-        if let CandlCurrentWrapper::PossiblyCurrent(ctx) = &self.ctx_ref() {
-            ctx.resize(nsize);
-            self.render.set_size((nsize.width, nsize.height));
+        self.size = nsize;
+        self.refresh_visibility();
+        self.render.set_size((nsize.width, nsize.height));
+        // the context is resized once it's current again
+        match &self.ctx {
+            Some(CandlCurrentWrapper::PossiblyCurrent(ctx)) => ctx.resize(nsize),
+            _ => self.resize_pending = true
         }
     }
+
+    /// handle scale factor change event, the renderer gets the new scale
+    /// factor before the new size
+    fn rescale(&mut self, scale_factor: f64, nsize: PhysicalSize<u32>) {
+        self.scale_factor = scale_factor;
+        self.render.set_scale_factor(scale_factor);
+        self.resize(nsize);
+    }
 }
 
 impl<'a, R> CandlElement<CandlSurface<R, CandlNoState, ()>> for CandlSurface<R, CandlNoState, ()>
@@ -615,31 +1095,247 @@ where R: CandlRenderer<R, D, M>, D: CandlUpdate<M> {
         mut render: R,
         init_state: D
     ) -> Result<Self, CandlError> {
-        let ctx = <CandlSurface<R, D, M>>::init(el, video_mode, dim, title, options)?;
-        render.set_scale_factor(ctx.window().scale_factor());
-        let ipsize = ctx.window().inner_size();
-        render.set_size((ipsize.width, ipsize.height));
+        let ctx = <CandlSurface<R, D, M>>::init(el, video_mode.clone(), dim, title, options)?;
+        let context = CandlContext::new(&ctx);
+        let gl_debug = if options.debug() {
+            Some(CandlGlDebug::install(context.window_id(), context.gl().clone()))
+        } else { None };
+        let scale_factor = ctx.window().scale_factor();
+        let size = ctx.window().inner_size();
+        // declared after the debug output, to be dropped before it on error
         let ctx = Some(CandlCurrentWrapper::PossiblyCurrent(ctx));
-        render.finalize();
+        render.setup(&context)?;
+        render.set_scale_factor(scale_factor);
+        render.set_size((size.width, size.height));
         Ok(CandlSurface {
             ctx,
             render,
             state: init_state,
+            context,
+            gl_debug,
+            keymap: CandlKeymap::default(),
+            drop_handler: None,
+            observers: Vec::new(),
+            middlewares: Vec::new(),
+            cursor: None,
+            text_input: false,
+            ime: false,
+            ime_area: None,
+            text_queue: VecDeque::default(),
+            scale_factor,
+            size,
+            resize_pending: false,
+            visible: size.width > 0 && size.height > 0,
+            hidden: false,
+            minimized: false,
+            maximized: false,
+            focused: false,
+            hovered: false,
+            close_pending: false,
+            closing: false,
+            video_mode,
+            dim,
+            title: title.to_string(),
+            options,
             message: PhantomData,
             redraw: false
         })
     }
 
+    /// recreate the window and its OpenGL context, the context must be the
+    /// current one
+    ///
+    /// glutin can't create a context for an existing window, so the window
+    /// is recreated too, with the same title, options and size, and a new id,
+    /// which is returned. The new context is created first, then the renderer
+    /// is torn down with the old one, and receives the new one with
+    /// `context_recreated()`. If a step fails, the new window is dropped, and
+    /// the renderer is set up again with the old context, which stays the
+    /// one of the surface.
+    pub fn recreate_context<T>(
+        &mut self,
+        el: &EventLoopWindowTarget<T>
+    ) -> Result<WindowId, CandlError> {
+        if let CandlCurrentWrapper::NotCurrent(_) = self.ctx_ref() {
+            return Err(CandlError::InternalError("The context of this surface is not the current context"));
+        }
+        let dim = match self.dim {
+            CandlDimension::Classic(_, _) => {
+                let size = self.logical_size();
+                CandlDimension::Classic(size.width.round() as u32, size.height.round() as u32)
+            }
+            dim => dim
+        };
+        // the creation of the new context makes it current
+        let res = <CandlSurface<R, D, M>>::init(
+            el, self.video_mode.clone(), dim, &self.title, self.options
+        );
+        let ctx = match res {
+            Ok(ctx) => ctx,
+            Err(err) => {
+                self.restore_current()?;
+                return Err(err);
+            }
+        };
+        self.restore_current()?;
+        self.render.teardown(&self.context);
+        let ctx = match unsafe { ctx.make_current() } {
+            Ok(ctx) => ctx,
+            Err((_, err)) => {
+                self.restore_current()?;
+                self.render.setup(&self.context)?;
+                return Err(CandlError::from(err));
+            }
+        };
+        let context = CandlContext::new(&ctx);
+        let mut gl_debug = if self.options.debug() {
+            Some(CandlGlDebug::install(context.window_id(), context.gl().clone()))
+        } else { None };
+        if let Err(err) = self.render.context_recreated(&context) {
+            // the debug output must be dropped after its context
+            drop(ctx);
+            drop(gl_debug);
+            self.restore_current()?;
+            self.render.setup(&self.context)?;
+            return Err(err);
+        }
+        if let (Some(old), Some(new)) = (&mut self.gl_debug, &mut gl_debug) {
+            new.take_handler_from(old);
+        }
+        self.scale_factor = ctx.window().scale_factor();
+        self.size = ctx.window().inner_size();
+        self.resize_pending = false;
+        // the old debug output must be dropped after the old context
+        self.ctx = Some(CandlCurrentWrapper::PossiblyCurrent(ctx));
+        self.gl_debug = gl_debug;
+        self.context = context;
+        self.dim = dim;
+        self.cursor = None;
+        self.render.set_scale_factor(self.scale_factor);
+        self.render.set_size((self.size.width, self.size.height));
+        Ok(self.context.window_id())
+    }
+
+    /// make the context of the surface current again, after the creation of
+    /// another one
+    fn restore_current(&mut self) -> Result<(), CandlError> {
+        match self.ctx() {
+            CandlCurrentWrapper::PossiblyCurrent(ctx) => match unsafe { ctx.make_current() } {
+                Ok(ctx) => {
+                    self.set_ctx(CandlCurrentWrapper::PossiblyCurrent(ctx));
+                    Ok(())
+                }
+                Err((ctx, err)) => {
+                    self.set_ctx(CandlCurrentWrapper::PossiblyCurrent(ctx));
+                    Err(CandlError::from(err))
+                }
+            },
+            wrapper => {
+                self.set_ctx(wrapper);
+                Ok(())
+            }
+        }
+    }
+
     /// change the title of the window
     pub fn title(&mut self, new_title: &str) {
-        match self.ctx.as_ref().unwrap() {
-            CandlCurrentWrapper::PossiblyCurrent(ctx) =>
-                ctx.window().set_title(new_title),
-            CandlCurrentWrapper::NotCurrent(ctx) =>
-                ctx.window().set_title(new_title)
-        };
+        self.title = new_title.to_string();
+        self.win_ref().set_title(new_title);
+    }
+
+    /// get the monitor the window is on
+    pub fn current_monitor(&self) -> CandlMonitor {
+        CandlMonitor::from(self.win_ref().current_monitor())
+    }
+
+    /// get the current scale factor of the surface
+    pub fn scale_factor(&self) -> f64 { self.scale_factor }
+
+    /// get the size of the surface in physical pixels, as used by OpenGL
+    pub fn physical_size(&self) -> PhysicalSize<u32> { self.size }
+
+    /// get the size of the surface in logical pixels
+    pub fn logical_size(&self) -> LogicalSize<f64> {
+        self.size.to_logical(self.scale_factor)
+    }
+
+    /// check if the surface is visible, i.e. its window isn't hidden nor
+    /// minimized
+    pub fn visible(&self) -> bool { self.visible }
+
+    /// notify the renderer if the visibility of the surface changed
+    fn refresh_visibility(&mut self) {
+        let visible = !self.hidden && !self.minimized
+            && self.size.width > 0 && self.size.height > 0;
+        if visible != self.visible {
+            self.visible = visible;
+            self.render.visibility_changed(visible);
+        }
+    }
+
+    /// check if the window has the keyboard focus
+    pub fn focused(&self) -> bool { self.focused }
+
+    /// check if the cursor is over the window
+    pub fn hovered(&self) -> bool { self.hovered }
+
+    /// show or hide the window
+    pub fn set_visible(&mut self, visible: bool) {
+        self.win_ref().set_visible(visible);
+        self.hidden = !visible;
+        self.refresh_visibility();
+    }
+
+    /// check if the window was minimized through the surface, and not
+    /// focused since
+    pub fn minimized(&self) -> bool { self.minimized }
+
+    /// minimize or restore the window
+    pub fn set_minimized(&mut self, minimized: bool) {
+        self.win_ref().set_minimized(minimized);
+        self.minimized = minimized;
+        self.refresh_visibility();
+    }
+
+    /// check if the window was maximized through the surface
+    pub fn maximized(&self) -> bool { self.maximized }
+
+    /// maximize the window, or restore its size
+    pub fn set_maximized(&mut self, maximized: bool) {
+        self.win_ref().set_maximized(maximized);
+        self.maximized = maximized;
+    }
+
+    /// show the window with its normal size, neither minimized nor maximized
+    pub fn restore(&mut self) {
+        self.set_maximized(false);
+        self.set_minimized(false);
+        self.set_visible(true);
     }
 
+    /// bring the window in front of the others, at best
+    ///
+    /// glutin can't raise a window nor give it the focus, so the window is
+    /// only shown and restored from its minimized state, which is enough for
+    /// most of the window managers to put it in front.
+    pub fn raise(&mut self) {
+        self.set_minimized(false);
+        self.set_visible(true);
+    }
+
+    /// get the handle of the surface context, as given to the renderer
+    pub fn context(&self) -> &CandlContext { &self.context }
+
+    /// get the OpenGL functions of the surface context
+    pub fn gl(&self) -> &CandlGl { self.context.gl() }
+
+    /// get the OpenGL debug output, if the debug option is enabled
+    pub fn gl_debug(&self) -> Option<&CandlGlDebug> { self.gl_debug.as_ref() }
+
+    /// get the OpenGL debug output as a mutable reference, to change its
+    /// handler
+    pub fn gl_debug_mut(&mut self) -> Option<&mut CandlGlDebug> { self.gl_debug.as_mut() }
+
     /// get the render object (immutable way)
     pub fn render(&self) -> &R { &self.render }
 
@@ -653,8 +1349,167 @@ where R: CandlRenderer<R, D, M>, D: CandlUpdate<M> {
     pub fn state_mut(&mut self) -> &mut D { &mut self.state }
 
     /// call the update method of the state (mandatory by CandlUpdate trait)
+    ///
+    /// The message goes first through the middlewares of the surface. For
+    /// each message reaching the state, the surface asks for a redraw if the
+    /// message affects the rendering, as told by
+    /// `CandlUpdate::update_redraw()`, then the observers of the state are
+    /// notified.
     pub fn update(&mut self, message: M) {
-        self.state.update(message);
+        let mut messages = vec![message];
+        for CandlMiddleware(middleware) in &mut self.middlewares {
+            let mut next = Vec::with_capacity(messages.len());
+            for message in messages { next.extend(middleware(message)); }
+            messages = next;
+        }
+        for message in messages {
+            if self.state.update_redraw(message) { self.ask_redraw(); }
+            let state = &self.state;
+            for CandlObserver(observer) in &mut self.observers { observer(state); }
+        }
+    }
+
+    /// add an observer of the state, notified after each message
+    pub fn observe<F>(&mut self, observer: F)
+    where F: FnMut(&D) + 'static {
+        self.observers.push(CandlObserver(Box::new(observer)));
+    }
+
+    /// remove all the observers of the state
+    pub fn clear_observers(&mut self) { self.observers.clear(); }
+
+    /// ask the state if the surface can be closed
+    ///
+    /// Once confirmed, `should_close()` is true, and the surface must be
+    /// dropped with its context current (done by `CandlManager`), to let the
+    /// renderer release its resources. A surface already confirmed doesn't
+    /// ask the state again.
+    pub fn request_close(&mut self) -> CandlCloseResponse {
+        if self.closing { return CandlCloseResponse::Confirm; }
+        let response = self.state.close_requested();
+        match response {
+            CandlCloseResponse::Confirm => self.confirm_close(),
+            CandlCloseResponse::Veto => self.close_pending = false,
+            CandlCloseResponse::Defer => self.close_pending = true
+        }
+        response
+    }
+
+    /// check if a close request was deferred by the state, and waits for an
+    /// answer
+    pub fn close_pending(&self) -> bool { self.close_pending }
+
+    /// confirm the close of the surface, pending or not
+    pub fn confirm_close(&mut self) {
+        self.close_pending = false;
+        self.closing = true;
+    }
+
+    /// cancel a pending close request, the surface stays open
+    pub fn cancel_close(&mut self) { self.close_pending = false; }
+
+    /// check if the close of the surface was confirmed
+    pub fn should_close(&self) -> bool { self.closing }
+
+    /// get the keymap of the surface
+    pub fn keymap(&self) -> &CandlKeymap<M> { &self.keymap }
+
+    /// get the keymap of the surface as a mutable reference
+    pub fn keymap_mut(&mut self) -> &mut CandlKeymap<M> { &mut self.keymap }
+
+    /// replace the keymap of the surface
+    pub fn set_keymap(&mut self, keymap: CandlKeymap<M>) { self.keymap = keymap; }
+
+    /// set the function converting the file drops into messages
+    pub fn set_drop_handler<F>(&mut self, handler: F)
+    where F: FnMut(CandlDrop) -> Option<M> + 'static {
+        self.drop_handler = Some(CandlDropHandler(Box::new(handler)));
+    }
+
+    /// remove the file drop handler, the file drops are then ignored
+    pub fn clear_drop_handler(&mut self) { self.drop_handler = None; }
+
+    /// get the last known position of the cursor over the surface, `None`
+    /// when the cursor isn't over the surface
+    pub fn cursor_position(&self) -> Option<PhysicalPosition<f64>> {
+        self.cursor.filter(|_| self.hovered)
+    }
+
+    /// check if the surface collects the text input
+    pub fn text_input_enabled(&self) -> bool { self.text_input }
+
+    /// start or stop collecting the text input (disabled by default)
+    ///
+    /// Stopping the text input also disable the IME and drop the text input
+    /// not read yet.
+    pub fn set_text_input(&mut self, enabled: bool) {
+        self.text_input = enabled;
+        if !enabled {
+            self.ime = false;
+            self.text_queue.clear();
+        }
+    }
+
+    /// check if the surface accepts the composition of an input method
+    pub fn ime_enabled(&self) -> bool { self.ime }
+
+    /// accept or refuse the composition of an input method (disabled by
+    /// default), enabling the IME also enable the text input
+    ///
+    /// Disabling the IME drops the preedit and commit inputs not read yet.
+    pub fn set_ime(&mut self, enabled: bool) {
+        self.ime = enabled;
+        if enabled {
+            self.text_input = true;
+            if let Some((position, _)) = self.ime_area {
+                self.win_ref().set_ime_position(position);
+            }
+        } else {
+            self.text_queue.retain(|input| match input {
+                CandlTextInput::Char(_) => true,
+                CandlTextInput::Preedit { .. } | CandlTextInput::Commit(_) => false
+            });
+        }
+    }
+
+    /// get the area of the text being edited, as set for the IME
+    pub fn ime_cursor_area(&self) -> Option<(PhysicalPosition<i32>, PhysicalSize<u32>)> {
+        self.ime_area
+    }
+
+    /// set the area of the text being edited, in physical pixels
+    ///
+    /// The input method use it to place its candidate window next to the text.
+    /// Only the position is forwarded to the window, glutin doesn't support
+    /// the size of the area yet.
+    pub fn set_ime_cursor_area(&mut self, position: PhysicalPosition<i32>, size: PhysicalSize<u32>) {
+        self.ime_area = Some((position, size));
+        if self.ime { self.win_ref().set_ime_position(position); }
+    }
+
+    /// add a text input to the stream of the surface
+    ///
+    /// The text input is ignored if it's disabled, and the preedit and commit
+    /// inputs are ignored if the IME is disabled.
+    pub fn push_text_input(&mut self, input: CandlTextInput) {
+        let accepted = match input {
+            CandlTextInput::Char(_) => self.text_input,
+            CandlTextInput::Preedit { .. } | CandlTextInput::Commit(_) => self.ime
+        };
+        if accepted { self.text_queue.push_back(input); }
+    }
+
+    /// read the text input received since the last call, in order
+    pub fn text_input(&mut self) -> impl Iterator<Item = CandlTextInput> + '_ {
+        self.text_queue.drain(..)
+    }
+
+    /// get the window, whatever the status of the context
+    fn win_ref(&self) -> &Window {
+        match self.ctx_ref() {
+            CandlCurrentWrapper::PossiblyCurrent(ctx) => ctx.window(),
+            CandlCurrentWrapper::NotCurrent(ctx) => ctx.window()
+        }
     }
 
     /// requesting the window to handle a redraw
@@ -673,12 +1528,80 @@ where R: CandlRenderer<R, D, M>, D: CandlUpdate<M> {
         self.redraw = false;
     }
 
-    /// draw on the surface
+    /// draw on the surface, logging the error if it fails, see `try_draw()`
     pub fn draw(&mut self) {
-        //
+        if let Err(err) = self.try_draw() { log::error!("Failed to draw the surface: {}", err); }
+    }
+
+    /// draw on the surface, or return an error if the context isn't the
+    /// current one or if the buffers can't be swapped
+    pub fn try_draw(&mut self) -> Result<(), CandlError> {
+        if let CandlCurrentWrapper::NotCurrent(_) = self.ctx_ref() {
+            return Err(CandlError::InternalError("The context of this surface is not the current context"));
+        }
         self.render.draw_frame(&self.state);
-        //
-        self.swap_buffers();
+        if let Some(debug) = &mut self.gl_debug { debug.poll(); }
+        if let CandlCurrentWrapper::PossiblyCurrent(ctx) = self.ctx_ref() {
+            ctx.swap_buffers()?;
+        }
+        Ok(())
+    }
+}
+
+impl<R, D, M> CandlSurface<R, D, M>
+where R: CandlRenderer<R, D, M>, D: CandlUpdate<M>, M: Clone {
+    /// convert a window event into a message
+    ///
+    /// The keys and the mouse buttons go through the keymap of the surface,
+    /// and the file drops through its drop handler. All the events of the
+    /// window must go through this method, to let the surface track the
+    /// modifiers, the keys held and the position of the cursor, and collect
+    /// the text input, and to notify the renderer of the focus changes.
+    pub fn map_input(&mut self, event: &WindowEvent) -> Option<M> {
+        let drop = match event {
+            WindowEvent::CursorMoved { position, .. } => {
+                self.cursor = Some(*position);
+                None
+            }
+            WindowEvent::CursorEntered { .. } => {
+                self.hovered = true;
+                None
+            }
+            WindowEvent::CursorLeft { .. } => {
+                self.cursor = None;
+                self.hovered = false;
+                None
+            }
+            // the moves aren't delivered during the drags, the enter and leave
+            // events tell if the last position is still over the surface
+            WindowEvent::HoveredFile(path) =>
+                Some(CandlDrop::Hovered { path: path.clone(), position: self.cursor_position() }),
+            WindowEvent::DroppedFile(path) =>
+                Some(CandlDrop::Dropped { path: path.clone(), position: self.cursor_position() }),
+            WindowEvent::HoveredFileCancelled => Some(CandlDrop::Cancelled),
+            WindowEvent::Focused(focused) => {
+                self.focused = *focused;
+                // a minimized window can't have the focus
+                if *focused && self.minimized {
+                    self.minimized = false;
+                    self.refresh_visibility();
+                }
+                self.render.focus_changed(*focused);
+                None
+            }
+            WindowEvent::ReceivedCharacter(c) => {
+                if !c.is_control() {
+                    self.push_text_input(CandlTextInput::Char(*c));
+                }
+                None
+            }
+            _ => None
+        };
+        match (drop, &mut self.drop_handler) {
+            (Some(drop), Some(CandlDropHandler(handler))) => handler(drop),
+            (Some(_), None) => None,
+            (None, _) => self.keymap.handle_event(event).cloned()
+        }
     }
 }
 
@@ -717,6 +1640,45 @@ pub trait CandlElement<W: CandlWindow> {
         options: CandlOptions) -> Result<W, CandlError>;
 }
 
+/// Focus change
+///
+/// Notified by the `CandlManager` when the keyboard focus or the cursor moves
+/// from a window to another.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CandlFocusChange {
+    /// the window gained the keyboard focus
+    Focused(WindowId),
+    /// the window lost the keyboard focus
+    Unfocused(WindowId),
+    /// the cursor entered the window
+    Entered(WindowId),
+    /// the cursor left the window
+    Left(WindowId)
+}
+
+/// number of focus changes kept by the manager until they're read, the
+/// oldest ones are dropped beyond
+const MAX_FOCUS_CHANGES: usize = 64;
+
+/// function routing the messages between the surfaces
+type CandlRouterFn<M> = Box<dyn FnMut(WindowId, M) -> Vec<(WindowId, M)>>;
+
+/// middleware of the manager, routing the messages between the surfaces
+struct CandlRouter<M>(CandlRouterFn<M>);
+
+/// the user key, tags and data of a managed window
+struct CandlWindowMeta<K> {
+    key: Option<K>,
+    tags: HashSet<String>,
+    data: Option<Box<dyn Any>>
+}
+
+impl<K> CandlWindowMeta<K> {
+    fn new() -> Self {
+        CandlWindowMeta { key: None, tags: HashSet::default(), data: None }
+    }
+}
+
 /// The window manager
 ///
 /// Second core element of this lib, the `CandlManager` is the tool to bring
@@ -729,25 +1691,72 @@ pub trait CandlElement<W: CandlWindow> {
 /// 3. call `get_current()` to swap contexts and get the window you can work with
 /// 4. done
 /// 
+/// The current window, the one with the current OpenGL context, isn't always
+/// the focused one. The manager tracks the keyboard focus and the cursor from
+/// the events given to `track_event()` (or `map_input()`), see
+/// `focused_window()` and `focus_changes()`.
+/// 
+/// Each window can also get a user key (a `String` by default, or the type
+/// given with `new_with_keys()`), some tags, and user data, to find the
+/// windows without keeping their `WindowId` in a side table.
+/// 
 /// Check the
 /// [candelabre examples](https://github.com/othelarian/candelabre/tree/master/candelabre-examples)
 /// to see it in action.
-pub struct CandlManager<W: CandlWindow, S> {
+pub struct CandlManager<W: CandlWindow, S, K = String> {
 //pub struct CandlManager<D, M> {
     current: Option<WindowId>,
     surfaces: HashMap<WindowId, Option<W>>,
+    keys: HashMap<K, WindowId>,
+    meta: HashMap<WindowId, CandlWindowMeta<K>>,
+    routers: Vec<CandlRouter<W::Message>>,
+    focused: Option<WindowId>,
+    hovered: Option<WindowId>,
+    focus_changes: VecDeque<CandlFocusChange>,
     state: S
 }
 
+impl<W: CandlWindow, S, K> Drop for CandlManager<W, S, K> {
+    /// make each context current in turn before dropping its surface, so all
+    /// the renderers are torn down
+    fn drop(&mut self) {
+        let ids: Vec<WindowId> = self.surfaces.keys().cloned().collect();
+        for id in ids {
+            let mut surface = match self.surfaces.remove(&id) {
+                Some(Some(surface)) => surface,
+                _ => continue
+            };
+            // even the context current at first, as the previous surfaces
+            // made their own one current since
+            let wrapper = match surface.ctx() {
+                CandlCurrentWrapper::NotCurrent(ctx) => match unsafe { ctx.make_current() } {
+                    Ok(ctx) => CandlCurrentWrapper::PossiblyCurrent(ctx),
+                    Err((ctx, _)) => CandlCurrentWrapper::NotCurrent(ctx)
+                },
+                CandlCurrentWrapper::PossiblyCurrent(ctx) => match unsafe { ctx.make_current() } {
+                    Ok(ctx) => CandlCurrentWrapper::PossiblyCurrent(ctx),
+                    Err((ctx, _)) => CandlCurrentWrapper::NotCurrent(unsafe { ctx.treat_as_not_current() })
+                }
+            };
+            surface.set_ctx(wrapper);
+            // the surface tears down its renderer if its context is current
+            drop(surface);
+        }
+    }
+}
+
 impl<W: CandlWindow> CandlManager<W, ()> {
     /// most default constructor for the manager
-    pub fn new() -> Self {
-        CandlManager { current: None, surfaces: HashMap::default(), state: () }
-    }
+    pub fn new() -> Self { CandlManager::new_with_state(()) }
 }
 
-impl<R, D, M, S> CandlManager<CandlSurface<R, D, M>, S>
-where R: CandlRenderer<R, D, M>, D: CandlUpdate<M> {
+impl<W: CandlWindow, S> CandlManager<W, S> {
+    /// constructor for the manager with state type link to it
+    pub fn new_with_state(init_state: S) -> Self { CandlManager::new_with_keys(init_state) }
+}
+
+impl<R, D, M, S, K> CandlManager<CandlSurface<R, D, M>, S, K>
+where R: CandlRenderer<R, D, M>, D: CandlUpdate<M>, K: Clone + Eq + Hash {
     /// create a new window from a CandlSurfaceBuilder
     pub fn create_window_from_builder<T>(
         &mut self,
@@ -772,9 +1781,142 @@ where R: CandlRenderer<R, D, M>, D: CandlUpdate<M> {
         let surface = CandlSurface::window_builder(el, video_mode, dim, title, options, render, init_state)?;
         self.add_window(surface)
     }
+
+    /// convert a window event into a message, with the surface it comes from
+    ///
+    /// Unlike `get_current()`, this method doesn't swap the OpenGL contexts.
+    /// It returns `None` if the window isn't managed by the manager. The
+    /// event is also given to `track_event()`.
+    pub fn map_input(&mut self, id: WindowId, event: &WindowEvent) -> Option<M>
+    where M: Clone {
+        self.track_event(id, event);
+        self.surfaces.get_mut(&id)?.as_mut()?.map_input(event)
+    }
+
+    /// add a middleware to the manager, called with each message given to
+    /// `update()`
+    ///
+    /// The middleware receives the message with the window it's for, and
+    /// returns the messages to pass on, each one with its window: none to
+    /// filter the message, another one or another window to transform it, or
+    /// several ones to fan it out, e.g. to mirror it to other windows. The
+    /// middlewares are called in the order they are added.
+    pub fn with_middleware<F>(mut self, middleware: F) -> Self
+    where F: FnMut(WindowId, M) -> Vec<(WindowId, M)> + 'static {
+        self.routers.push(CandlRouter(Box::new(middleware)));
+        self
+    }
+
+    /// give a message to the state of a surface, through the middlewares of
+    /// the manager, then through the ones of the surface
+    ///
+    /// Unlike `get_current()`, this method doesn't swap the OpenGL contexts.
+    /// The messages for a window not managed by the manager are dropped.
+    pub fn update(&mut self, id: WindowId, message: M) {
+        let mut messages = vec![(id, message)];
+        for CandlRouter(router) in &mut self.routers {
+            let mut next = Vec::with_capacity(messages.len());
+            for (id, message) in messages { next.extend(router(id, message)); }
+            messages = next;
+        }
+        for (id, message) in messages {
+            if let Some(Some(surface)) = self.surfaces.get_mut(&id) { surface.update(message); }
+        }
+    }
+
+    /// ask the state of a surface if it can be closed, the surface is
+    /// removed if the close is confirmed
+    ///
+    /// It returns `None` if the window isn't managed by the manager.
+    pub fn request_close(&mut self, id: WindowId) -> Option<CandlCloseResponse> {
+        let response = self.surfaces.get_mut(&id)?.as_mut()?.request_close();
+        if response == CandlCloseResponse::Confirm { self.remove_window(id); }
+        Some(response)
+    }
+
+    /// ask all the surfaces if they can be closed, and remove the confirmed
+    /// ones, then return the windows still open
+    ///
+    /// Useful to quit the application: it can exit if nothing is returned.
+    pub fn request_close_all(&mut self) -> Vec<WindowId> {
+        for id in self.list_window_ids() { self.request_close(id); }
+        self.list_window_ids()
+    }
+
+    /// list the windows with a pending close request
+    pub fn pending_closes(&self) -> Vec<WindowId> {
+        self.surfaces.iter()
+            .filter_map(|(id, s)| match s {
+                Some(s) if s.close_pending() => Some(*id),
+                _ => None
+            })
+            .collect()
+    }
+
+    /// confirm the close of a surface, which is removed
+    ///
+    /// It returns `false` if the window isn't managed by the manager.
+    pub fn confirm_close(&mut self, id: WindowId) -> bool {
+        if !self.surfaces.contains_key(&id) { return false; }
+        self.remove_window(id);
+        true
+    }
+
+    /// cancel the pending close request of a surface
+    pub fn cancel_close(&mut self, id: WindowId) {
+        if let Some(Some(surface)) = self.surfaces.get_mut(&id) { surface.cancel_close(); }
+    }
+
+    /// draw all the surfaces, see `for_each_current()` for the order and
+    /// the errors
+    pub fn draw_all(&mut self) -> Vec<(WindowId, CandlError)> {
+        let ids = self.list_window_ids();
+        self.visit_current(ids, |_, surface| surface.try_draw())
+    }
+
+    /// draw the visible surfaces which asked for a redraw, and reset their
+    /// redraw request
+    ///
+    /// Unlike `request_redraw()`, the surfaces are drawn right away, without
+    /// waiting for a `RedrawRequested` event. See `for_each_current()` for the
+    /// order and the errors.
+    pub fn draw_dirty(&mut self) -> Vec<(WindowId, CandlError)> {
+        let ids = self.surfaces.iter()
+            .filter_map(|(id, s)| match s {
+                Some(s) if s.redraw && s.visible => Some(*id),
+                _ => None
+            })
+            .collect();
+        self.visit_current(ids, |_, surface| {
+            surface.redraw = false;
+            surface.try_draw()
+        })
+    }
+
+    /// recreate the window and the OpenGL context of a surface
+    ///
+    /// See `CandlSurface::recreate_context()`: the window gets a new id, and
+    /// the manager tracks the surface with this new id, which is returned.
+    pub fn recreate_context<T>(
+        &mut self,
+        id: WindowId,
+        el: &EventLoopWindowTarget<T>
+    ) -> Result<WindowId, CandlError> {
+        let res = self.get_current(id)?.recreate_context(el);
+        let surface = self.surfaces.remove(&id).unwrap();
+        let new_id = surface.as_ref().unwrap().context().window_id();
+        self.surfaces.insert(new_id, surface);
+        self.current = Some(new_id);
+        if let Some(meta) = self.meta.remove(&id) {
+            if let Some(key) = &meta.key { self.keys.insert(key.clone(), new_id); }
+            self.meta.insert(new_id, meta);
+        }
+        if new_id != id { self.forget_focus(id); }
+        res
+    }
 }
 
-impl<W: CandlWindow, S> CandlManager<W, S> {
+impl<W: CandlWindow, S, K: Clone + Eq + Hash> CandlManager<W, S, K> {
     /// create a new window, tracked by the manager
     /// 
     /// For internal reason, it isn't possible to add a `CandlSurface` manually
@@ -800,11 +1942,18 @@ impl<W: CandlWindow, S> CandlManager<W, S> {
         self.add_window(surface)
     }
 
-    /// constructor for the manager with state type link to it
-    pub fn new_with_state(init_state: S) -> Self {
+    /// constructor for the manager with state type link to it, and a user
+    /// key type other than `String`
+    pub fn new_with_keys(init_state: S) -> Self {
         CandlManager {
             current: None,
             surfaces: HashMap::default(),
+            keys: HashMap::default(),
+            meta: HashMap::default(),
+            routers: Vec::new(),
+            focused: None,
+            hovered: None,
+            focus_changes: VecDeque::default(),
             state: init_state
         }
     }
@@ -830,6 +1979,7 @@ impl<W: CandlWindow, S> CandlManager<W, S> {
                 }
                 surface.set_ctx(surface_ctx);
                 self.surfaces.insert(win_id, Some(surface));
+                self.meta.insert(win_id, CandlWindowMeta::new());
                 self.current = Some(win_id);
                 Ok(win_id)
             }
@@ -847,12 +1997,244 @@ impl<W: CandlWindow, S> CandlManager<W, S> {
     /// remove a window from the manager
     /// 
     /// If you don't call this method after closing a window, the OpenGL
-    /// context continue to exist, and can lead to memory leaks.
+    /// context continue to exist, and can lead to memory leaks. The context of
+    /// the window is made current before its removal, to let the renderer
+    /// release its resources.
     pub fn remove_window(&mut self, id: WindowId) {
+        if !self.surfaces.contains_key(&id) { return; }
+        let _ = self.get_current(id);
         if Some(id) == self.current { self.current.take(); }
+        self.forget_focus(id);
+        if let Some(CandlWindowMeta { key: Some(key), .. }) = self.meta.remove(&id) {
+            self.keys.remove(&key);
+        }
         self.surfaces.remove(&id);
     }
 
+    /// set the user key of a window, replacing its previous key
+    ///
+    /// A key can only be used by one window at a time.
+    pub fn set_key(&mut self, id: WindowId, key: K) -> Result<(), CandlError> {
+        match self.keys.get(&key) {
+            Some(other) if *other != id =>
+                return Err(CandlError::InternalError("This key is already used by another window")),
+            _ => ()
+        }
+        let meta = self.meta.get_mut(&id)
+            .ok_or(CandlError::InternalError("This window isn't managed by the manager"))?;
+        if let Some(old_key) = meta.key.replace(key.clone()) { self.keys.remove(&old_key); }
+        self.keys.insert(key, id);
+        Ok(())
+    }
+
+    /// remove the user key of a window, and return it
+    pub fn clear_key(&mut self, id: WindowId) -> Option<K> {
+        let key = self.meta.get_mut(&id)?.key.take()?;
+        self.keys.remove(&key);
+        Some(key)
+    }
+
+    /// get the user key of a window
+    pub fn key(&self, id: WindowId) -> Option<&K> { self.meta.get(&id)?.key.as_ref() }
+
+    /// find a window from its user key
+    pub fn find<Q>(&self, key: &Q) -> Option<WindowId>
+    where K: Borrow<Q>, Q: Eq + Hash + ?Sized {
+        self.keys.get(key).cloned()
+    }
+
+    /// get a mutable reference to a surface from its user key, see
+    /// `get_current()`
+    pub fn get_current_by_key<Q>(&mut self, key: &Q) -> Result<&mut W, CandlError>
+    where K: Borrow<Q>, Q: Eq + Hash + ?Sized {
+        let id = self.find(key)
+            .ok_or(CandlError::InternalError("No window with this key in the manager"))?;
+        self.get_current(id)
+    }
+
+    /// add a tag to a window, ignored if the window isn't managed
+    pub fn add_tag(&mut self, id: WindowId, tag: &str) {
+        if let Some(meta) = self.meta.get_mut(&id) { meta.tags.insert(tag.to_string()); }
+    }
+
+    /// remove a tag from a window
+    pub fn remove_tag(&mut self, id: WindowId, tag: &str) {
+        if let Some(meta) = self.meta.get_mut(&id) { meta.tags.remove(tag); }
+    }
+
+    /// check if a window has a tag
+    pub fn has_tag(&self, id: WindowId, tag: &str) -> bool {
+        match self.meta.get(&id) {
+            Some(meta) => meta.tags.contains(tag),
+            None => false
+        }
+    }
+
+    /// list the tags of a window
+    pub fn tags(&self, id: WindowId) -> Vec<&str> {
+        match self.meta.get(&id) {
+            Some(meta) => meta.tags.iter().map(|tag| tag.as_str()).collect(),
+            None => Vec::new()
+        }
+    }
+
+    /// list the windows with a tag
+    pub fn find_tagged(&self, tag: &str) -> Vec<WindowId> {
+        self.meta.iter()
+            .filter(|(_, meta)| meta.tags.contains(tag))
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    /// set the user data of a window, ignored if the window isn't managed
+    ///
+    /// The data lives as long as the window stays in the manager.
+    pub fn set_user_data<T: Any>(&mut self, id: WindowId, data: T) {
+        if let Some(meta) = self.meta.get_mut(&id) { meta.data = Some(Box::new(data)); }
+    }
+
+    /// get the user data of a window, if it has the asked type
+    pub fn user_data<T: Any>(&self, id: WindowId) -> Option<&T> {
+        self.meta.get(&id)?.data.as_ref()?.downcast_ref()
+    }
+
+    /// get the user data of a window as a mutable reference, if it has the
+    /// asked type
+    pub fn user_data_mut<T: Any>(&mut self, id: WindowId) -> Option<&mut T> {
+        self.meta.get_mut(&id)?.data.as_mut()?.downcast_mut()
+    }
+
+    /// remove the user data of a window and return it, if it has the asked
+    /// type (the data is kept otherwise)
+    pub fn take_user_data<T: Any>(&mut self, id: WindowId) -> Option<T> {
+        let meta = self.meta.get_mut(&id)?;
+        match meta.data.take()?.downcast() {
+            Ok(data) => Some(*data),
+            Err(data) => {
+                meta.data = Some(data);
+                None
+            }
+        }
+    }
+
+    /// get a mutable reference to a surface, see `get_current()`, with its
+    /// user data if it has the asked type
+    pub fn get_current_with_data<T: Any>(&mut self, id: WindowId)
+    -> Result<(&mut W, Option<&mut T>), CandlError> {
+        self.get_current(id)?;
+        let surface = self.surfaces.get_mut(&id).unwrap().as_mut().unwrap();
+        let data = self.meta.get_mut(&id)
+            .and_then(|meta| meta.data.as_mut())
+            .and_then(|data| data.downcast_mut());
+        Ok((surface, data))
+    }
+
+    /// call a function on each surface, with its context current
+    ///
+    /// The current surface goes first, then each other surface needs only one
+    /// context switch. A surface whose context can't be made current is
+    /// skipped, and its error is returned with its id, without stopping the
+    /// loop. The last surface visited stays the current one.
+    pub fn for_each_current<F>(&mut self, mut f: F) -> Vec<(WindowId, CandlError)>
+    where F: FnMut(WindowId, &mut W) {
+        let ids = self.list_window_ids();
+        self.visit_current(ids, |id, surface| {
+            f(id, surface);
+            Ok(())
+        })
+    }
+
+    /// internal method visiting some surfaces, the current one first
+    fn visit_current<F>(&mut self, mut ids: Vec<WindowId>, mut f: F) -> Vec<(WindowId, CandlError)>
+    where F: FnMut(WindowId, &mut W) -> Result<(), CandlError> {
+        ids.sort();
+        if let Some(current) = self.current {
+            if let Some(pos) = ids.iter().position(|id| *id == current) {
+                let id = ids.remove(pos);
+                ids.insert(0, id);
+            }
+        }
+        let mut errors = Vec::new();
+        for id in ids {
+            if let Err(err) = self.get_current(id).and_then(|surface| f(id, surface)) {
+                errors.push((id, err));
+            }
+        }
+        errors
+    }
+
+    /// get a surface without swapping the OpenGL contexts
+    ///
+    /// Only for what doesn't need the context, like the window controls. Use
+    /// `get_current()` for everything else.
+    pub fn get_surface(&mut self, id: WindowId) -> Option<&mut W> {
+        self.surfaces.get_mut(&id)?.as_mut()
+    }
+
+    /// track the keyboard focus and the cursor across the windows
+    ///
+    /// All the events of the windows should go through this method, or
+    /// through `map_input()` which calls it.
+    pub fn track_event(&mut self, id: WindowId, event: &WindowEvent) {
+        if !self.surfaces.contains_key(&id) { return; }
+        match event {
+            WindowEvent::Focused(true) if self.focused != Some(id) => {
+                if let Some(old_id) = self.focused.replace(id) {
+                    self.push_focus_change(CandlFocusChange::Unfocused(old_id));
+                }
+                self.push_focus_change(CandlFocusChange::Focused(id));
+            }
+            WindowEvent::Focused(false) if self.focused == Some(id) => {
+                self.focused = None;
+                self.push_focus_change(CandlFocusChange::Unfocused(id));
+            }
+            WindowEvent::CursorEntered { .. } if self.hovered != Some(id) => {
+                if let Some(old_id) = self.hovered.replace(id) {
+                    self.push_focus_change(CandlFocusChange::Left(old_id));
+                }
+                self.push_focus_change(CandlFocusChange::Entered(id));
+            }
+            WindowEvent::CursorLeft { .. } if self.hovered == Some(id) => {
+                self.hovered = None;
+                self.push_focus_change(CandlFocusChange::Left(id));
+            }
+            _ => ()
+        }
+    }
+
+    /// queue a focus change, dropping the oldest one if the queue is full
+    fn push_focus_change(&mut self, change: CandlFocusChange) {
+        if self.focus_changes.len() == MAX_FOCUS_CHANGES { self.focus_changes.pop_front(); }
+        self.focus_changes.push_back(change);
+    }
+
+    /// release the focus and the cursor of a window going away
+    fn forget_focus(&mut self, id: WindowId) {
+        if self.focused == Some(id) {
+            self.focused = None;
+            self.push_focus_change(CandlFocusChange::Unfocused(id));
+        }
+        if self.hovered == Some(id) {
+            self.hovered = None;
+            self.push_focus_change(CandlFocusChange::Left(id));
+        }
+    }
+
+    /// get the window with the keyboard focus, if it's a managed one
+    pub fn focused_window(&self) -> Option<WindowId> { self.focused }
+
+    /// get the window under the cursor, if it's a managed one
+    pub fn hovered_window(&self) -> Option<WindowId> { self.hovered }
+
+    /// read the focus changes since the last call, in order
+    ///
+    /// Only the last 64 changes are kept, read them after each batch of
+    /// events. A window removed, or recreated with a new id, loses the focus
+    /// and the cursor with `Unfocused` and `Left`.
+    pub fn focus_changes(&mut self) -> impl Iterator<Item = CandlFocusChange> + '_ {
+        self.focus_changes.drain(..)
+    }
+
     /// check if there is still living windows, or if the manager is empty
     ///
     /// The purpose of this method is to check if the application can be close,
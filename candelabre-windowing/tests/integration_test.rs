@@ -1,6 +1,11 @@
 use candelabre_windowing::*;
+use glutin::dpi::{LogicalSize, PhysicalPosition, PhysicalSize};
+use glutin::event::{DeviceId, ModifiersState, MouseButton, VirtualKeyCode, WindowEvent};
 use glutin::event_loop::EventLoop;
+use std::cell::{Cell, RefCell};
 use std::marker::PhantomData;
+use std::path::PathBuf;
+use std::rc::Rc;
 
 // ===========================================================
 // struct for the test
@@ -20,7 +25,7 @@ where S: CandlUpdate<M> {
         }
     }
 
-    fn finalize(&mut self) {}
+    fn setup(&mut self, _: &CandlContext) -> Result<(), CandlError> { Ok(()) }
 
     fn set_scale_factor(&mut self, _: f64) {}
 
@@ -45,8 +50,78 @@ impl FakeState {
 
 type FakeStateFakeGraphics = FakeGraphics<FakeState, ()>;
 
+struct ClosingState {
+    answer: CandlCloseResponse
+}
+
+impl CandlUpdate<CandlCloseResponse> for ClosingState {
+    fn update(&mut self, answer: CandlCloseResponse) { self.answer = answer; }
+
+    fn close_requested(&mut self) -> CandlCloseResponse { self.answer }
+}
+
+type ClosingSurface = CandlSurface<
+    FakeGraphics<ClosingState, CandlCloseResponse>,
+    ClosingState,
+    CandlCloseResponse
+>;
+
 type FakeSurface = CandlSurface<NoStateFakeGraphics, CandlNoState, ()>;
 
+struct SizedGraphics {
+    scale_factor: f64,
+    size: (u32, u32)
+}
+
+impl CandlRenderer<SizedGraphics, CandlNoState, ()> for SizedGraphics {
+    fn init() -> Self { Self { scale_factor: 0.0, size: (0, 0) } }
+
+    fn setup(&mut self, _: &CandlContext) -> Result<(), CandlError> { Ok(()) }
+
+    fn set_scale_factor(&mut self, scale_factor: f64) { self.scale_factor = scale_factor; }
+
+    fn set_size(&mut self, nsize: (u32, u32)) { self.size = nsize; }
+
+    fn draw_frame(&mut self, _: &CandlNoState) {}
+}
+
+type SizedSurface = CandlSurface<SizedGraphics, CandlNoState, ()>;
+
+thread_local! {
+    static CONTEXTS: Cell<u32> = const { Cell::new(0) };
+    static TEARDOWNS: RefCell<Vec<(u32, f32)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// renderer marking its context with a clear color of its own, to check
+/// which context is current during its teardown
+struct MarkedGraphics {
+    mark: u32
+}
+
+impl CandlRenderer<MarkedGraphics, CandlNoState, ()> for MarkedGraphics {
+    fn init() -> Self { Self { mark: 0 } }
+
+    fn setup(&mut self, ctx: &CandlContext) -> Result<(), CandlError> {
+        self.mark = CONTEXTS.with(|count| { count.set(count.get() + 1); count.get() });
+        unsafe { ctx.gl().ClearColor(self.mark as f32 / 10.0, 0.0, 0.0, 1.0); }
+        Ok(())
+    }
+
+    fn set_scale_factor(&mut self, _: f64) {}
+
+    fn set_size(&mut self, _: (u32, u32)) {}
+
+    fn draw_frame(&mut self, _: &CandlNoState) {}
+
+    fn teardown(&mut self, ctx: &CandlContext) {
+        let mut color = [0.0; 4];
+        unsafe { ctx.gl().GetFloatv(candl_gl::COLOR_CLEAR_VALUE, color.as_mut_ptr()); }
+        TEARDOWNS.with(|teardowns| teardowns.borrow_mut().push((self.mark, color[0])));
+    }
+}
+
+type MarkedSurface = CandlSurface<MarkedGraphics, CandlNoState, ()>;
+
 // ===========================================================
 // integrations test
 // ===========================================================
@@ -71,7 +146,7 @@ fn create_window() -> Result<(), String> {
     let el = EventLoop::new();
     match <CandlSurface<NoStateFakeGraphics, CandlNoState, ()>>::new(
         &el,
-        el.primary_monitor().video_modes().next().unwrap(),
+        CandlMonitor::primary(&el).select_mode(&CandlModeCriteria::default()).unwrap(),
         CandlDimension::Classic(800, 400),
         "test candelabre window",
         CandlOptions::default(),
@@ -87,7 +162,7 @@ fn create_window_with_state() -> Result<(), String> {
     let el = EventLoop::new();
     match CandlSurface::new_with_state(
         &el,
-        el.primary_monitor().video_modes().next().unwrap(),
+        CandlMonitor::primary(&el).select_mode(&CandlModeCriteria::default()).unwrap(),
         CandlDimension::Fullscreen,
         &String::from("test window with data"),
         CandlOptions::default(),
@@ -99,6 +174,135 @@ fn create_window_with_state() -> Result<(), String> {
     }
 }
 
+#[test]
+fn redraw_and_observe_after_update() -> Result<(), String> {
+    let el = EventLoop::new();
+    let updates = Rc::new(Cell::new(0));
+    let observed = updates.clone();
+    let mut surface = CandlSurfaceBuilder::new()
+        .render(FakeStateFakeGraphics::init())
+        .state(FakeState { value: 42 })
+        .observe(move |_| observed.set(observed.get() + 1))
+        .build(&el)
+        .map_err(|e| format!("{}", e))?;
+    if surface.check_redraw() {
+        return Err(String::from("Test failed: redraw asked before any update"));
+    }
+    surface.update(());
+    if !surface.check_redraw() {
+        Err(String::from("Test failed: no redraw asked after the update"))
+    } else if updates.get() != 1 {
+        Err(String::from("Test failed: observer not notified"))
+    } else { Ok(()) }
+}
+
+#[test]
+fn filter_and_fan_out_messages() -> Result<(), String> {
+    let el = EventLoop::new();
+    let updates = Rc::new(Cell::new(0));
+    let observed = updates.clone();
+    let mut surface = CandlSurfaceBuilder::new()
+        .render(FakeGraphics::init())
+        .state(ClosingState { answer: CandlCloseResponse::Confirm })
+        .middleware(|answer| match answer {
+            CandlCloseResponse::Veto => vec![],
+            answer => vec![answer, answer]
+        })
+        .observe(move |_| observed.set(observed.get() + 1))
+        .build(&el)
+        .map_err(|e| format!("{}", e))?;
+    surface.update(CandlCloseResponse::Veto);
+    surface.update(CandlCloseResponse::Defer);
+    if updates.get() != 2 {
+        Err(format!("Test failed: {} updates instead of 2", updates.get()))
+    } else if surface.state().answer != CandlCloseResponse::Defer {
+        Err(String::from("Test failed: filtered message reached the state"))
+    } else { Ok(()) }
+}
+
+#[test]
+#[allow(deprecated)]
+fn track_cursor_with_enter_and_leave() -> Result<(), String> {
+    let el = EventLoop::new();
+    let drops = Rc::new(RefCell::new(Vec::new()));
+    let received = drops.clone();
+    let mut surface = CandlSurfaceBuilder::new()
+        .render(NoStateFakeGraphics::init())
+        .no_state()
+        .on_drop(move |drop| {
+            received.borrow_mut().push(drop);
+            None
+        })
+        .build(&el)
+        .map_err(|e| format!("{}", e))?;
+    let device_id = unsafe { DeviceId::dummy() };
+    let position = PhysicalPosition::new(10.0, 20.0);
+    let path = PathBuf::from("dropped.txt");
+    surface.map_input(&WindowEvent::CursorEntered { device_id });
+    surface.map_input(&WindowEvent::CursorMoved {
+        device_id,
+        position,
+        modifiers: ModifiersState::empty()
+    });
+    surface.map_input(&WindowEvent::HoveredFile(path.clone()));
+    // no move during the drag, only the leave event
+    surface.map_input(&WindowEvent::CursorLeft { device_id });
+    surface.map_input(&WindowEvent::DroppedFile(path.clone()));
+    let expected = vec![
+        CandlDrop::Hovered { path: path.clone(), position: Some(position) },
+        CandlDrop::Dropped { path, position: None }
+    ];
+    if surface.hovered() || surface.cursor_position().is_some() {
+        Err(String::from("Test failed: cursor still over the surface after leaving it"))
+    } else if *drops.borrow() != expected {
+        Err(format!("Test failed: wrong drops {:?}", drops.borrow()))
+    } else { Ok(()) }
+}
+
+#[test]
+fn drop_ime_input_when_disabled() -> Result<(), String> {
+    let el = EventLoop::new();
+    let mut surface = CandlSurfaceBuilder::new()
+        .render(NoStateFakeGraphics::init())
+        .no_state()
+        .build(&el)
+        .map_err(|e| format!("{}", e))?;
+    surface.push_text_input(CandlTextInput::Char('a'));
+    if surface.text_input().next().is_some() {
+        return Err(String::from("Test failed: text input collected while disabled"));
+    }
+    surface.set_ime(true);
+    surface.push_text_input(CandlTextInput::Char('b'));
+    surface.push_text_input(CandlTextInput::Preedit { text: String::from("ka"), cursor: None });
+    surface.push_text_input(CandlTextInput::Commit(String::from("か")));
+    surface.set_ime(false);
+    surface.push_text_input(CandlTextInput::Commit(String::from("き")));
+    let inputs: Vec<CandlTextInput> = surface.text_input().collect();
+    if !surface.text_input_enabled() || surface.ime_enabled() {
+        Err(String::from("Test failed: wrong text input or IME state"))
+    } else if inputs != vec![CandlTextInput::Char('b')] {
+        Err(format!("Test failed: wrong text input {:?}", inputs))
+    } else { Ok(()) }
+}
+
+#[test]
+fn rescale_the_renderer() -> Result<(), String> {
+    let el = EventLoop::new();
+    let mut surface = CandlSurfaceBuilder::new()
+        .render(SizedGraphics::init())
+        .no_state()
+        .build(&el)
+        .map_err(|e| format!("{}", e))?;
+    surface.rescale(2.0, PhysicalSize::new(1600, 800));
+    if surface.render().scale_factor != 2.0 || surface.render().size != (1600, 800) {
+        Err(String::from("Test failed: the renderer didn't get the new scale factor and size"))
+    } else if surface.physical_size() != PhysicalSize::new(1600, 800) {
+        Err(format!("Test failed: wrong physical size {:?}", surface.physical_size()))
+    } else if surface.logical_size() != LogicalSize::new(800.0, 400.0) {
+        Err(format!("Test failed: wrong logical size {:?}", surface.logical_size()))
+    } else { Ok(()) }
+}
+
 #[test]
 fn open_multi_windows() -> Result<(), String> {
     let el = EventLoop::new();
@@ -106,7 +310,7 @@ fn open_multi_windows() -> Result<(), String> {
     for win_idx in 0..3 {
         &win_manager.create_window::<_, FakeSurface>(
             &el,
-            el.primary_monitor().video_modes().next().unwrap(),
+            CandlMonitor::primary(&el).select_mode(&CandlModeCriteria::default()).unwrap(),
             CandlDimension::Classic(800, 400),
             &format!("test candelabre multi window: #{}", win_idx+1),
             CandlOptions::default()
@@ -125,3 +329,234 @@ fn open_multi_windows() -> Result<(), String> {
     if win_manager.is_empty() { Ok(()) }
     else { Err(String::from("Test failed: CandlManager not empty!")) }
 }
+
+#[test]
+#[allow(deprecated)]
+fn notify_focus_changes_of_removed_windows() -> Result<(), String> {
+    let el = EventLoop::new();
+    let mut win_manager: CandlManager<FakeSurface, ()> = CandlManager::new();
+    let mut ids = vec!();
+    for win_idx in 0..2 {
+        ids.push(win_manager.create_window::<_, FakeSurface>(
+            &el,
+            CandlMonitor::primary(&el).select_mode(&CandlModeCriteria::default()).unwrap(),
+            CandlDimension::Classic(800, 400),
+            &format!("test candelabre focus: #{}", win_idx+1),
+            CandlOptions::default()
+        ).map_err(|e| format!("{}", e))?);
+    }
+    let device_id = unsafe { DeviceId::dummy() };
+    win_manager.track_event(ids[0], &WindowEvent::Focused(true));
+    win_manager.track_event(ids[0], &WindowEvent::CursorEntered { device_id });
+    win_manager.remove_window(ids[0]);
+    let changes: Vec<CandlFocusChange> = win_manager.focus_changes().collect();
+    for _ in 0..100 {
+        win_manager.track_event(ids[1], &WindowEvent::Focused(true));
+        win_manager.track_event(ids[1], &WindowEvent::Focused(false));
+    }
+    let flood: Vec<CandlFocusChange> = win_manager.focus_changes().collect();
+    let expected = vec![
+        CandlFocusChange::Focused(ids[0]),
+        CandlFocusChange::Entered(ids[0]),
+        CandlFocusChange::Unfocused(ids[0]),
+        CandlFocusChange::Left(ids[0])
+    ];
+    if changes != expected {
+        Err(format!("Test failed: wrong focus changes {:?}", changes))
+    } else if win_manager.focused_window().is_some() || win_manager.hovered_window().is_some() {
+        Err(String::from("Test failed: removed window still focused or hovered"))
+    } else if flood.len() != 64 || flood.last() != Some(&CandlFocusChange::Unfocused(ids[1])) {
+        Err(format!("Test failed: {} focus changes kept", flood.len()))
+    } else { Ok(()) }
+}
+
+#[test]
+fn draw_all_windows() -> Result<(), String> {
+    let el = EventLoop::new();
+    let mut win_manager: CandlManager<FakeSurface, ()> = CandlManager::new();
+    for win_idx in 0..3 {
+        win_manager.create_window::<_, FakeSurface>(
+            &el,
+            CandlMonitor::primary(&el).select_mode(&CandlModeCriteria::default()).unwrap(),
+            CandlDimension::Classic(800, 400),
+            &format!("test candelabre draw all: #{}", win_idx+1),
+            CandlOptions::default()
+        ).map_err(|e| format!("{}", e))?;
+    }
+    let mut visited = 0;
+    let errors = win_manager.for_each_current(|_, surface| {
+        surface.ask_redraw();
+        visited += 1;
+    });
+    if visited != 3 || !errors.is_empty() {
+        return Err(String::from("Test failed: not all the windows were visited"));
+    }
+    match (win_manager.draw_dirty().first(), win_manager.draw_all().first()) {
+        (None, None) => Ok(()),
+        (Some((_, e)), _) | (_, Some((_, e))) => Err(format!("{}", e))
+    }
+}
+
+#[test]
+fn resize_windows_not_current() -> Result<(), String> {
+    let el = EventLoop::new();
+    let mut win_manager: CandlManager<SizedSurface, ()> = CandlManager::new();
+    let mut ids = vec!();
+    for win_idx in 0..2 {
+        ids.push(win_manager.create_window::<_, SizedSurface>(
+            &el,
+            CandlMonitor::primary(&el).select_mode(&CandlModeCriteria::default()).unwrap(),
+            CandlDimension::Classic(800, 400),
+            &format!("test candelabre resize: #{}", win_idx+1),
+            CandlOptions::default()
+        ).map_err(|e| format!("{}", e))?);
+    }
+    // the last window created is the current one
+    let surface = win_manager.get_surface(ids[0]).ok_or("Test failed: no first window")?;
+    surface.resize(PhysicalSize::new(640, 320));
+    if surface.render().size != (640, 320) {
+        return Err(format!("Test failed: the renderer kept the size {:?}", surface.render().size));
+    }
+    let surface = win_manager.get_current(ids[0]).map_err(|e| format!("{}", e))?;
+    if surface.physical_size() != PhysicalSize::new(640, 320) || surface.render().size != (640, 320) {
+        Err(String::from("Test failed: the size was lost when the window became current"))
+    } else { Ok(()) }
+}
+
+#[test]
+fn tear_down_each_window_with_its_context() -> Result<(), String> {
+    let el = EventLoop::new();
+    let mut win_manager: CandlManager<MarkedSurface, ()> = CandlManager::new();
+    let mut ids = vec!();
+    for win_idx in 0..3 {
+        ids.push(win_manager.create_window::<_, MarkedSurface>(
+            &el,
+            CandlMonitor::primary(&el).select_mode(&CandlModeCriteria::default()).unwrap(),
+            CandlDimension::Classic(800, 400),
+            &format!("test candelabre teardown: #{}", win_idx+1),
+            CandlOptions::default()
+        ).map_err(|e| format!("{}", e))?);
+    }
+    win_manager.get_current(ids[0]).map_err(|e| format!("{}", e))?;
+    win_manager.remove_window(ids[1]);
+    drop(win_manager);
+    let mut teardowns = TEARDOWNS.with(|teardowns| teardowns.borrow().clone());
+    teardowns.sort_by_key(|(mark, _)| *mark);
+    let marks: Vec<u32> = teardowns.iter().map(|(mark, _)| *mark).collect();
+    if marks != vec![1, 2, 3] {
+        Err(format!("Test failed: wrong teardowns {:?}", marks))
+    } else if let Some((mark, _)) = teardowns.iter()
+        .find(|(mark, red)| (*red - *mark as f32 / 10.0).abs() > 0.001) {
+        Err(format!("Test failed: window #{} torn down with another context", mark))
+    } else { Ok(()) }
+}
+
+#[test]
+fn veto_and_defer_close_requests() -> Result<(), String> {
+    let el = EventLoop::new();
+    let mut win_manager: CandlManager<ClosingSurface, ()> = CandlManager::new();
+    let id = win_manager.create_window_with_state(
+        &el,
+        CandlMonitor::primary(&el).select_mode(&CandlModeCriteria::default()).unwrap(),
+        CandlDimension::Classic(800, 400),
+        "test candelabre close request",
+        CandlOptions::default(),
+        FakeGraphics::init(),
+        ClosingState { answer: CandlCloseResponse::Veto }
+    ).map_err(|e| format!("{}", e))?;
+    if win_manager.request_close(id) != Some(CandlCloseResponse::Veto) || win_manager.is_empty() {
+        return Err(String::from("Test failed: vetoed close removed the window"));
+    }
+    win_manager.get_current(id).unwrap().update(CandlCloseResponse::Defer);
+    win_manager.request_close(id);
+    if win_manager.pending_closes() != vec![id] {
+        return Err(String::from("Test failed: deferred close not pending"));
+    }
+    win_manager.confirm_close(id);
+    if win_manager.is_empty() { Ok(()) }
+    else { Err(String::from("Test failed: confirmed close didn't remove the window")) }
+}
+
+#[test]
+fn find_windows_by_key_and_tag() -> Result<(), String> {
+    let el = EventLoop::new();
+    let mut win_manager: CandlManager<FakeSurface, ()> = CandlManager::new();
+    let mut ids = Vec::new();
+    for win_idx in 0..2 {
+        ids.push(win_manager.create_window::<_, FakeSurface>(
+            &el,
+            CandlMonitor::primary(&el).select_mode(&CandlModeCriteria::default()).unwrap(),
+            CandlDimension::Classic(800, 400),
+            &format!("test candelabre keys: #{}", win_idx+1),
+            CandlOptions::default()
+        ).map_err(|e| format!("{}", e))?);
+    }
+    win_manager.set_key(ids[0], String::from("main")).map_err(|e| format!("{}", e))?;
+    if win_manager.set_key(ids[1], String::from("main")).is_ok() {
+        return Err(String::from("Test failed: same key used by two windows"));
+    }
+    win_manager.add_tag(ids[1], "tool");
+    win_manager.set_user_data(ids[1], 42u32);
+    if win_manager.find("main") != Some(ids[0])
+        || win_manager.find_tagged("tool") != vec![ids[1]]
+        || win_manager.user_data::<u32>(ids[1]) != Some(&42)
+        || win_manager.user_data::<String>(ids[1]).is_some() {
+        return Err(String::from("Test failed: wrong key, tag or user data"));
+    }
+    win_manager.remove_window(ids[0]);
+    if win_manager.find("main").is_some() {
+        Err(String::from("Test failed: key still used after removing the window"))
+    } else { Ok(()) }
+}
+
+// ===========================================================
+// keymap tests
+// ===========================================================
+
+#[derive(Clone, Debug, PartialEq)]
+enum KeyMessage {
+    Quit,
+    Save
+}
+
+fn parse_key_message(name: &str) -> Option<KeyMessage> {
+    match name {
+        "quit" => Some(KeyMessage::Quit),
+        "save" => Some(KeyMessage::Save),
+        _ => None
+    }
+}
+
+#[test]
+fn load_keymap_from_config() -> Result<(), String> {
+    let keymap = CandlKeymap::from_config(
+        "# test keymap\nEscape:released = quit\nctrl+S = save\n",
+        parse_key_message
+    ).map_err(|e| format!("{}", e))?;
+    let quit = CandlChord::key(VirtualKeyCode::Escape)
+        .set_trigger(CandlTrigger::Released);
+    let save = CandlChord::key(VirtualKeyCode::S)
+        .set_modifiers(ModifiersState::CTRL);
+    if keymap.get(&quit) != Some(&KeyMessage::Quit) {
+        Err(String::from("Test failed: 'Escape:released' not bound"))
+    } else if keymap.get(&save) != Some(&KeyMessage::Save) {
+        Err(String::from("Test failed: 'ctrl+S' not bound"))
+    } else if keymap.get(&CandlChord::key(VirtualKeyCode::S)).is_some() {
+        Err(String::from("Test failed: 'S' without modifiers is bound"))
+    } else { Ok(()) }
+}
+
+#[test]
+fn detect_keymap_conflicts() -> Result<(), String> {
+    let res = CandlKeymap::from_config(
+        "ctrl+S = save\nCTRL + s : pressed = quit\n",
+        parse_key_message
+    );
+    let mut keymap = CandlKeymap::new();
+    keymap.bind(CandlChord::mouse(MouseButton::Left), KeyMessage::Save).unwrap();
+    let mouse_res = keymap.bind(CandlChord::mouse(MouseButton::Left), KeyMessage::Quit);
+    match (res, mouse_res) {
+        (Err(CandlError::KeymapError(_)), Err(CandlError::KeymapError(_))) => Ok(()),
+        _ => Err(String::from("Test failed: conflicting bindings accepted"))
+    }
+}